@@ -1,20 +1,55 @@
-use std::{env, str::FromStr, sync::LazyLock, thread};
+use std::{
+    env,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
 
 use anyhow::{Error, Result};
-use ark_invest_api_rust_data::{Ark, Source, util::ticker::Ticker};
+use ark_invest_api_rust_data::{
+    ALIASES, Ark, COMPANY_RULES, Source,
+    util::{identifier, ticker::Ticker},
+};
+use chrono::NaiveDate;
 use clokwerk::{AsyncScheduler, Job, TimeUnits};
-use futures::future::join_all;
 use polars::prelude::DataFrame;
 use rand::Rng;
 use strum::IntoEnumIterator;
-use tokio::{task, time::Duration};
+use tokio::{
+    sync::Semaphore,
+    task::{self, JoinSet},
+    time::Duration,
+};
 
-static SOURCE: LazyLock<Source> = LazyLock::new(|| {
-    env::var("ARK_SOURCE").map_or(Source::ApiIncremental, |val| {
-        Source::from_str(&val).expect("Env string ARK_SOURCE is not in enum Source")
-    })
+static SOURCE: LazyLock<Vec<Source>> = LazyLock::new(|| {
+    env::var("ARK_SOURCE").map_or_else(
+        |_| vec![Source::ApiIncremental],
+        |val| Source::parse_chain(&val).expect("Env string ARK_SOURCE is not in enum Source"),
+    )
 });
 
+// ARK_SOURCE_{ticker} overrides ARK_SOURCE for one ticker
+fn sources(ticker: Ticker) -> Vec<Source> {
+    env::var(format!("ARK_SOURCE_{ticker}")).map_or_else(
+        |_| SOURCE.clone(),
+        |val| {
+            Source::parse_chain(&val)
+                .unwrap_or_else(|e| panic!("Env string ARK_SOURCE_{ticker} is invalid: {e}"))
+        },
+    )
+}
+
+// How many tickers download and format at the same time
+fn concurrency() -> Result<usize, Error> {
+    let concurrency = env::var("ARK_CONCURRENCY").map_or(Ok(4), |val| {
+        val.parse()
+            .map_err(|_| anyhow::anyhow!("Env string ARK_CONCURRENCY is not a number"))
+    })?;
+    if concurrency == 0 {
+        return Err(anyhow::anyhow!("ARK_CONCURRENCY must be at least 1"));
+    }
+    Ok(concurrency)
+}
+
 fn print_df(ticker: Ticker, df: &DataFrame) {
     println!(
         "Ticker: {:#?}\nShape: {:?}\n{:#?}",
@@ -40,79 +75,226 @@ fn csv_merge() -> Result<(), Error> {
     Ok(())
 }
 
-fn ark_plan(ticker: Ticker) -> Result<(), Error> {
-    println!("Starting: {ticker:#?}");
-    let sec = Duration::from_secs(rand::rng().random_range(30 * 60..=4 * 60 * 60));
-    // sleep(sec).await;
-    thread::sleep(sec);
-
-    let df = Ark::new(*SOURCE, ticker, None)
-        .map_err(|e| anyhow::anyhow!("Failed to create Ark instance for {ticker}: {e}"))?
-        .format()
-        .map_err(|e| anyhow::anyhow!("Failed to format data for {ticker}: {e}"))?
-        .write_parquet()
-        .map_err(|e| anyhow::anyhow!("Failed to write parquet for {ticker}: {e}"))?
-        .collect()
-        .map_err(|e| anyhow::anyhow!("Failed to collect data for {ticker}: {e}"))?;
-
-    print_df(ticker, &df);
+fn rebuild() -> Result<(), Error> {
+    for ticker in Ticker::iter() {
+        if !Ark::has_archive(ticker, None) {
+            continue;
+        }
+
+        let df = Ark::rebuild_from_archive(ticker, None)?
+            .format()?
+            .sort()?
+            .write_parquet()?
+            .collect()?;
+        print_df(ticker, &df);
+    }
     Ok(())
 }
 
-async fn spawn_ark_plan(ticker: Ticker) -> Result<(), Error> {
-    task::spawn_blocking(move || {
-        if let Err(e) = ark_plan(ticker) {
-            eprintln!("Error processing ticker {ticker:?}: {e:?}");
+fn backfill_source() -> Result<Source, Error> {
+    env::var("BACKFILL_SOURCE").map_or(Ok(Source::ApiIncremental), |val| {
+        Source::from_str(&val).map_err(|_| anyhow::anyhow!("BACKFILL_SOURCE is not in enum Source"))
+    })
+}
+
+async fn fill_gaps() -> Result<(), Error> {
+    let source = backfill_source()?;
+    for ticker in Ticker::iter() {
+        if !std::path::Path::new(&format!("./data/parquet/{ticker}.parquet")).exists() {
+            continue;
+        }
+
+        let mut ark = Ark::open(ticker, None, Ark::default_transport()?)?;
+        if let Err(e) = ark.fill_gaps(source).await {
+            eprintln!("Error filling gaps for ticker {ticker:?}: {e:?}");
+            continue;
         }
+        let df = ark.sort()?.write_parquet()?.collect()?;
+        print_df(ticker, &df);
+    }
+    Ok(())
+}
+
+// Runs the company name rules over every name in the parquet history and
+// prints the ones that come out damaged, renamed, or split from their history
+fn check_companies() -> Result<(), Error> {
+    let (mut total, mut splits) = (0, 0);
+    for ticker in Ticker::iter() {
+        if !std::path::Path::new(&format!("./data/parquet/{ticker}.parquet")).exists() {
+            continue;
+        }
+
+        let ark = Ark::open(ticker, None, Ark::default_transport()?)?;
+        for x in COMPANY_RULES.mangled(Some(ticker), &ark.companies()?)? {
+            println!("{ticker}: {:?} -> {:?}", x.before, x.after);
+            total += 1;
+        }
+
+        let changes = ark.company_changes()?;
+        for (before, after) in &changes.rewritten {
+            println!("{ticker}: {before:?} is rewritten to {after:?}");
+        }
+        for (before, after) in &changes.split {
+            println!("{ticker}: {before:?} in the history, {after:?} in new rows");
+            splits += 1;
+        }
+    }
+    println!("{total} company names would be damaged, {splits} would split from their history");
+    Ok(())
+}
+
+// Fetches BACKFILL_START..=BACKFILL_END for BACKFILL_TICKERS, or every ticker
+async fn backfill(start: &str, end: &str) -> Result<(), Error> {
+    let start = NaiveDate::from_str(start)?;
+    let end = NaiveDate::from_str(end)?;
+    let source = backfill_source()?;
+    let tickers = match env::var("BACKFILL_TICKERS") {
+        Ok(val) => val
+            .split(',')
+            .map(|x| Ticker::from_str(x.trim()))
+            .collect::<Result<Vec<_>, _>>()?,
+        Err(_) => Ticker::iter().collect(),
+    };
+
+    for ticker in tickers {
+        let ark = Ark::open(ticker, None, Ark::default_transport()?)?;
+        if let Err(e) = ark.backfill(start, end, source).await {
+            eprintln!("Error backfilling ticker {ticker:?}: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn ark_plan(ticker: Ticker, permits: Arc<Semaphore>) -> Result<(), Error> {
+    println!("Starting: {ticker:#?}");
+    let sec = Duration::from_secs(rand::rng().random_range(30 * 60..=4 * 60 * 60));
+    tokio::time::sleep(sec).await;
+
+    let _permit = permits.acquire().await?;
+    let ark = Ark::new_with_fallback(&sources(ticker), ticker, None, Ark::default_transport()?)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to create Ark instance for {ticker}: {e}"))?;
+    println!("{ticker}: fetched from {:?}", ark.source());
+
+    // polars work, off the runtime so timers and other tickers keep running
+    task::spawn_blocking(move || {
+        let df = ark
+            .format()
+            .map_err(|e| anyhow::anyhow!("Failed to format data for {ticker}: {e}"))?
+            .write_parquet()
+            .map_err(|e| anyhow::anyhow!("Failed to write parquet for {ticker}: {e}"))?
+            .collect()
+            .map_err(|e| anyhow::anyhow!("Failed to collect data for {ticker}: {e}"))?;
+
+        print_df(ticker, &df);
+        println!(
+            "{ticker}: identifiers {}",
+            identifier::Report::from_df(&df)?
+        );
+        Ok::<_, Error>(())
     })
-    .await
-    .unwrap();
+    .await??;
     Ok(())
 }
 
-async fn ark_etf() {
-    let futures = Ticker::iter()
-        .filter(|&x| {
-            x != Ticker::ARKA
-                && x != Ticker::ARKC
-                && x != Ticker::ARKD
-                && x != Ticker::ARKY
-                && x != Ticker::ARKZ
-                && x != Ticker::ARKB
-                && x != Ticker::PRNT
-                && x != Ticker::EUROPE_ARKI
-                && x != Ticker::EUROPE_ARKG
-                && x != Ticker::EUROPE_ARKK
-                && x != Ticker::CYBR
-                && x != Ticker::CYCL
-                && x != Ticker::FOOD
-                && x != Ticker::LIFE
-                && x != Ticker::LUSA
-                && x != Ticker::NFRA
-                && x != Ticker::PMNT
-        })
-        .map(spawn_ark_plan)
-        .collect::<Vec<_>>();
-
-    join_all(futures).await;
+async fn ark_etf(concurrency: usize) {
+    let permits = Arc::new(Semaphore::new(concurrency));
+    let tickers = Ticker::iter().filter(|&x| {
+        x != Ticker::ARKA
+            && x != Ticker::ARKC
+            && x != Ticker::ARKD
+            && x != Ticker::ARKY
+            && x != Ticker::ARKZ
+            && x != Ticker::ARKB
+            && x != Ticker::PRNT
+            && x != Ticker::EUROPE_ARKI
+            && x != Ticker::EUROPE_ARKG
+            && x != Ticker::EUROPE_ARKK
+            && x != Ticker::CYBR
+            && x != Ticker::CYCL
+            && x != Ticker::FOOD
+            && x != Ticker::LIFE
+            && x != Ticker::LUSA
+            && x != Ticker::NFRA
+            && x != Ticker::PMNT
+    });
+
+    // dropping the set, when the run is cancelled, aborts every ticker still running
+    let mut tasks = JoinSet::new();
+    for ticker in tickers {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            if let Err(e) = ark_plan(ticker, permits).await {
+                eprintln!("Error processing ticker {ticker:?}: {e:?}");
+            }
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        if let Err(e) = result {
+            eprintln!("Ticker task failed: {e:?}");
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn run() -> Result<(), Error> {
     let mut scheduler = AsyncScheduler::new();
     println!("Scheduler Started");
+    let concurrency = concurrency()?;
+
+    // fail now on a broken rules or aliases file, not halfway through the first run
+    println!(
+        "Loaded {} company name rules, version {}",
+        COMPANY_RULES.rules.len(),
+        COMPANY_RULES.version
+    );
+    println!(
+        "Loaded {} ticker aliases, version {}",
+        ALIASES.aliases.len(),
+        ALIASES.version
+    );
+
+    if env::var("STARTUP_CHECK_COMPANIES").is_ok_and(|v| v == "true") {
+        println!("Checking company name rules against Parquet history");
+        check_companies().map_err(|e| anyhow::anyhow!("Failed company check: {e}"))?;
+    }
 
     if env::var("STARTUP_CSV_MERGE").is_ok_and(|v| v == "true") {
         println!("Merging CSVs to Parquet");
         csv_merge().map_err(|e| anyhow::anyhow!("Failed CSV merge: {e}"))?;
     }
 
+    if env::var("STARTUP_REBUILD").is_ok_and(|v| v == "true") {
+        println!("Rebuilding Parquet from raw archive");
+        rebuild().map_err(|e| anyhow::anyhow!("Failed rebuild: {e}"))?;
+    }
+
+    if let (Ok(start), Ok(end)) = (env::var("BACKFILL_START"), env::var("BACKFILL_END")) {
+        println!("Backfilling {start} to {end}");
+        backfill(&start, &end)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed backfill: {e}"))?;
+    }
+
+    if env::var("STARTUP_FILL_GAPS").is_ok_and(|v| v == "true") {
+        println!("Filling gaps in Parquet history");
+        fill_gaps()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fill gaps: {e}"))?;
+    }
+
     if env::var("STARTUP_ARK_ETF").is_ok_and(|v| v == "true") {
-        ark_etf().await;
+        ark_etf(concurrency).await;
     }
 
-    scheduler.every(1.day()).at("10:00 am").run(ark_etf);
-    scheduler.every(1.day()).at("10:00 pm").run(ark_etf);
+    scheduler
+        .every(1.day())
+        .at("10:00 am")
+        .run(move || ark_etf(concurrency));
+    scheduler
+        .every(1.day())
+        .at("10:00 pm")
+        .run(move || ark_etf(concurrency));
 
     loop {
         scheduler.run_pending().await;
@@ -120,3 +302,14 @@ async fn main() -> Result<(), Error> {
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tokio::select! {
+        result = run() => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!("Shutting down");
+            Ok(())
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use std::{fs, sync::Arc};
+
 use anyhow::{Error, Result};
 use polars::prelude::*;
 
+use crate::util::transport::{FixtureTransport, Transport, fixture_name};
+
 pub fn defualt_df(ticker: &[Option<&str>], company: &[Option<&str>]) -> Result<DataFrame, Error> {
     let target_len = ticker.len() + 1;
     let df = df![
@@ -15,3 +19,115 @@ pub fn defualt_df(ticker: &[Option<&str>], company: &[Option<&str>]) -> Result<D
     ]?;
     Ok(df)
 }
+
+// ARK csv with one TESLA holding on 10/16/2026
+pub const TESLA_CSV: &str = "date,fund,company,ticker,cusip,shares,\"market value ($)\",\"weight (%)\"\n\
+     10/16/2026,ARKK,\"TESLA INC\",TSLA,88160R101,\"1,000\",\"$250,000.00\",10.50%\n";
+
+// Api json with one TESLA holding on every date
+pub fn tesla_json(dates: &[&str]) -> String {
+    let rows: Vec<_> = dates
+        .iter()
+        .map(|date| {
+            format!(
+                r#"{{"company":"TESLA","cusip":"88160R101","date":"{date}","market_value":200000,"share_price":200.0,"shares":1000,"ticker":"TSLA","weight":10.5,"weight_rank":1}}"#
+            )
+        })
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+// Directory under data/test, removed with everything in it when dropped
+pub struct TestDir(String);
+
+impl TestDir {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let dir = format!("data/test/{name}");
+        fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.0
+    }
+
+    // Answer to `url` for the FixtureTransport of this directory
+    pub fn fixture(&self, url: &str, body: impl AsRef<[u8]>) -> Result<(), Error> {
+        fs::write(format!("{}/{}", self.0, fixture_name(url)), body)?;
+        Ok(())
+    }
+
+    // Status and headers of the answer to `url`
+    pub fn fixture_meta(&self, url: &str, meta: &str) -> Result<(), Error> {
+        fs::write(format!("{}/{}.meta.json", self.0, fixture_name(url)), meta)?;
+        Ok(())
+    }
+
+    pub fn transport(&self) -> Arc<dyn Transport> {
+        Arc::new(FixtureTransport::new(&self.0))
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Minimal xlsx workbook with one sheet, every cell is an inline string and
+// empty strings are left out
+pub fn xlsx(rows: &[&[&str]]) -> Result<Vec<u8>, Error> {
+    use std::{
+        fmt::Write as _,
+        io::{Cursor, Write},
+    };
+
+    use ::zip::{ZipWriter, write::SimpleFileOptions};
+
+    let mut sheet = String::from(
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (r, row) in rows.iter().enumerate() {
+        write!(sheet, r#"<row r="{}">"#, r + 1)?;
+        for (c, value) in row.iter().enumerate().filter(|(_, x)| !x.is_empty()) {
+            let cell = format!("{}{}", char::from(b'A' + u8::try_from(c)?), r + 1);
+            let value = value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            write!(
+                sheet,
+                r#"<c r="{cell}" t="inlineStr"><is><t>{value}</t></is></c>"#
+            )?;
+        }
+        sheet.push_str("</row>");
+    }
+    sheet.push_str("</sheetData></worksheet>");
+
+    let files = [
+        (
+            "[Content_Types].xml",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#.to_owned(),
+        ),
+        (
+            "_rels/.rels",
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_owned(),
+        ),
+        (
+            "xl/workbook.xml",
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Holdings" sheetId="1" r:id="rId1"/></sheets></workbook>"#.to_owned(),
+        ),
+        (
+            "xl/_rels/workbook.xml.rels",
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#.to_owned(),
+        ),
+        ("xl/worksheets/sheet1.xml", sheet),
+    ];
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, content) in files {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
@@ -1,20 +1,51 @@
 use std::{
+    collections::HashMap,
     fs::{File, create_dir_all},
     path::Path,
+    str::FromStr,
+    sync::Arc,
 };
 
 use anyhow::{Error, Result, anyhow};
-use chrono::{Duration, NaiveDate};
-use data_reader::Reader;
+use archive::Archive;
+use cache::HttpCache;
+use chrono::{Duration, Local, Months, NaiveDate};
+use data_reader::{NotModified, Reader};
 use df::{DF, DFS};
 use glob::glob;
 use polars::{datatypes::DataType, lazy::dsl::StrptimeOptions, prelude::*};
+use rate_limit::{RATE_LIMIT, RateLimited};
+use retry::{Retry, RetryPolicy};
+use schema::{RAW_COLUMNS, SCHEMA, Schema};
+use security_master::SecurityMaster;
+use serde_json::{Value, json};
+use strum::IntoEnumIterator;
 use strum_macros::EnumString;
 use ticker::{DataSource, Ticker};
+use transport::{
+    FixtureTransport, HttpTransport, Mode, RecordReplayTransport, Request, Response, Transport,
+};
+use upstream::UPSTREAM;
+pub mod archive;
+pub mod asset_class;
+pub mod cache;
 pub mod data_reader;
 pub mod df;
 mod format;
+pub mod gaps;
+pub mod identifier;
+pub mod rate_limit;
+pub mod retry;
+pub mod schema;
+pub mod security_master;
 pub mod ticker;
+pub mod transport;
+pub mod upstream;
+
+pub use format::{alias::ALIASES, company::COMPANY_RULES};
+
+// Size of one request made by Ark::backfill
+const BACKFILL_CHUNK_MONTHS: u32 = 3;
 
 #[derive(Debug, Default, EnumString, Clone, Copy, PartialEq, Eq)]
 pub enum Source {
@@ -33,17 +64,82 @@ pub enum Source {
     ArkFundsIoFull,
 }
 
+impl Source {
+    // Comma separated, in the order they are tried, ex "Ark,ApiIncremental"
+    pub fn parse_chain(val: &str) -> Result<Vec<Self>, Error> {
+        let chain = val
+            .split(',')
+            .map(|x| Self::from_str(x.trim()).map_err(|_| anyhow!("{x} is not in enum Source")))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if chain.is_empty() {
+            return Err(anyhow!("Source chain is empty"));
+        }
+        Ok(chain)
+    }
+}
+
+/// Stored company names that come out different with the current aliases and
+/// rules, as (stored, now) pairs of the same cusip on the same day
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CompanyChanges {
+    // fixed in the history by the next write
+    pub rewritten: Vec<(String, String)>,
+    // the history keeps its name and new rows get another one, an alias is
+    // needed to keep the security together
+    pub split: Vec<(String, String)>,
+}
+
 #[derive(Clone)]
 pub struct Ark {
     pub df: DF,
     ticker: Ticker,
     path: Option<String>,
+    transport: Arc<dyn Transport>,
+    // Source returned 304, the parquet file is already up to date
+    unchanged: bool,
+    // Source the data was fetched from, None when built from files
+    source: Option<Source>,
+    // Url and response of the csv download, its validators are only cached
+    // once the parquet file is written
+    validators: Option<(String, Response)>,
 }
 impl Ark {
-    pub fn new(source: Source, ticker: Ticker, path: Option<String>) -> Result<Self, Error> {
+    pub async fn new(source: Source, ticker: Ticker, path: Option<String>) -> Result<Self, Error> {
+        Self::new_with_transport(source, ticker, path, Self::default_transport()?).await
+    }
+
+    // The network, or a directory of saved responses picked by ARK_TRANSPORT
+    pub fn default_transport() -> Result<Arc<dyn Transport>, Error> {
+        // every attempt of a retry waits for the rate limit
+        let http = || -> Result<Arc<dyn Transport>, Error> {
+            Ok(Arc::new(Retry::new(
+                RateLimited::new(HttpTransport::new()?, &RATE_LIMIT),
+                RetryPolicy::from_env()?,
+            )))
+        };
+
+        Ok(match transport::Kind::from_env()? {
+            transport::Kind::Http => http()?,
+            transport::Kind::Fixtures(dir) => Arc::new(FixtureTransport::new(dir)),
+            transport::Kind::Record(dir) => {
+                Arc::new(RecordReplayTransport::new(dir, Mode::Record, http()?))
+            }
+            transport::Kind::Replay(dir) => {
+                Arc::new(RecordReplayTransport::new(dir, Mode::Replay, http()?))
+            }
+        })
+    }
+
+    // Reads the parquet file if it exists, without fetching anything
+    pub fn open(
+        ticker: Ticker,
+        path: Option<String>,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, Error> {
         let existing_file = Self::read_parquet(ticker, path.as_ref()).is_ok();
 
-        let mut ark = Self {
+        Ok(Self {
             df: if existing_file {
                 Self::read_parquet(ticker, path.as_ref())?
             } else {
@@ -51,30 +147,81 @@ impl Ark {
             },
             ticker,
             path,
-        };
+            transport,
+            unchanged: false,
+            source: None,
+            validators: None,
+        })
+    }
+
+    // Tries every source in order, until one succeeds
+    pub async fn new_with_fallback(
+        sources: &[Source],
+        ticker: Ticker,
+        path: Option<String>,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, Error> {
+        let mut errors = vec![];
+        for &source in sources {
+            match Self::new_with_transport(source, ticker, path.clone(), transport.clone()).await {
+                Ok(ark) => return Ok(ark),
+                Err(e) => {
+                    eprintln!("{ticker}: {source:?} failed, {e}");
+                    errors.push(format!("{source:?}: {e}"));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Every source failed for {ticker}: [{}]",
+            errors.join(", ")
+        ))
+    }
+
+    pub async fn new_with_transport(
+        source: Source,
+        ticker: Ticker,
+        path: Option<String>,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, Error> {
+        let existing_file = Self::read_parquet(ticker, path.as_ref()).is_ok();
+        let mut ark = Self::open(ticker, path, transport)?;
+        ark.source = Some(source);
 
         let update = match (source, existing_file) {
             (Source::Read, false) => {
-                panic!("Can not read from file. file is empty, does not exist, or is locked")
+                return Err(anyhow!(
+                    "Can not read from file. file is empty, does not exist, or is locked"
+                ));
             }
             (Source::Read, true) => None,
-            (Source::Ark, _) => Some(ark.get_csv_ark()?),
+            (Source::Ark, _) => match ark.get_csv_ark().await {
+                Ok(df) => Some(df),
+                Err(e) if e.is::<NotModified>() => {
+                    println!("{e}, skipping {ticker}");
+                    ark.unchanged = true;
+                    None
+                }
+                Err(e) => return Err(e),
+            },
             (Source::ApiIncremental | Source::ArkFundsIoIncremental, true) => {
                 let last_day = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
                     + Duration::days(ark.df.clone().collect()?.column("date")?.max().unwrap());
-                Some(ark.get_api(Some(last_day), Some(&source))?)
+                Some(ark.get_api(Some(last_day), None, Some(&source)).await?)
             }
-            _ => Some(ark.get_api(None, Some(&source))?),
+            // one request, Ark::backfill fetches a window in chunks
+            _ => Some(ark.get_api(None, None, Some(&source)).await?),
         };
 
         if let Some(update) = update {
+            // only the csv from ark follows the schema of the data source
+            let data_source = (source == Source::Ark).then(|| ticker.data_source());
+            let update = Self::df_format(update.into(), Some(ticker), data_source)?;
             if existing_file {
-                ark.df = Self::concat_df(vec![
-                    Self::df_format(ark.df, None)?,
-                    Self::df_format(update.into(), None)?,
-                ])?;
+                ark.df =
+                    Self::concat_df(vec![Self::df_format(ark.df, Some(ticker), None)?, update])?;
             } else {
-                ark.df = Self::df_format(update.into(), None)?;
+                ark.df = update;
             }
         }
 
@@ -85,18 +232,67 @@ impl Ark {
         self.df.collect()
     }
 
+    #[must_use]
+    pub const fn is_unchanged(&self) -> bool {
+        self.unchanged
+    }
+
+    #[must_use]
+    pub const fn source(&self) -> Option<Source> {
+        self.source
+    }
+
+    fn data_dir(&self, name: &str) -> String {
+        Self::data_dir_in(self.path.as_deref(), name)
+    }
+
+    fn data_dir_in(path: Option<&str>, name: &str) -> String {
+        path.map_or_else(|| format!("data/{name}"), |p| format!("{p}/{name}"))
+    }
+
+    /// Whether [`Ark::rebuild_from_archive`] has any archived payload or old
+    /// csv to read for the ticker
+    #[must_use]
+    pub fn has_archive(ticker: Ticker, path: Option<&str>) -> bool {
+        ["raw", "csv"]
+            .iter()
+            .any(|name| Path::new(&format!("{}/{ticker}", Self::data_dir_in(path, name))).exists())
+    }
+
     pub fn write_parquet(self) -> Result<Self, Error> {
+        if self.unchanged {
+            return Ok(self);
+        }
         // with format df
         let ark = self.format()?;
-        let path_str = if let Some(ref path) = ark.path {
-            format!("{}/{}.parquet", path, ark.ticker)
-        } else {
-            format!("data/parquet/{}.parquet", ark.ticker)
-        };
-        Self::write_df_parquet(&path_str, ark.df.clone())?;
+        let df = ark.df.clone().collect()?;
+        Self::write_df_parquet(&ark.parquet_path(), df.clone().into())?;
+        let securities = ark.securities_path();
+        if !Path::new(&securities).exists() {
+            security_master::seed_securities(&securities, ark.fund_parquets())?;
+        }
+        security_master::update_securities(&securities, ark.ticker, &df)?;
+        if let Some((url, response)) = &ark.validators {
+            HttpCache::new(ark.data_dir("cache")).store(url, response)?;
+        }
         Ok(ark)
     }
 
+    fn parquet_path(&self) -> String {
+        self.path.as_ref().map_or_else(
+            || format!("data/parquet/{}.parquet", self.ticker),
+            |path| format!("{path}/{}.parquet", self.ticker),
+        )
+    }
+
+    // Next to the parquet file of every fund
+    fn securities_path(&self) -> String {
+        self.path.as_ref().map_or_else(
+            || "data/parquet/securities.parquet".to_owned(),
+            |path| format!("{path}/securities.parquet"),
+        )
+    }
+
     fn write_df_parquet(path: &str, df: DF) -> Result<(), Error> {
         if let Some(parent) = Path::new(&path).parent()
             && !parent.exists()
@@ -145,22 +341,104 @@ impl Ark {
     }
 
     pub fn format(mut self) -> Result<Self, Error> {
-        // self.df = Self::df_format(self.df, Some(self.ticker.data_source()))?;
-        self.df = Self::df_format(self.df, None)?;
+        if self.unchanged {
+            return Ok(self);
+        }
+        // already formatted or merged with the parquet file, not a raw download
+        let mut df = Self::df_format(self.df.clone(), Some(self.ticker), None)?.collect()?;
+
+        let europe = matches!(
+            self.ticker.data_source(),
+            DataSource::ArkEurope | DataSource::Rize
+        );
+        let isins = df.get_column_names().contains(&"isin");
+        if europe || isins {
+            let master = self.security_master()?;
+            if europe {
+                let filled;
+                (df, filled) = master.fill(df)?;
+                println!("{}: {filled}", self.ticker);
+            }
+            if isins {
+                df = master.fill_isins(df)?;
+            }
+        }
+        self.df = df.into();
         Ok(self)
     }
 
-    pub fn df_format(df: DF, data_source: Option<DataSource>) -> Result<DF, Error> {
+    // Parquet file of every other fund that has one
+    fn fund_parquets(&self) -> Vec<(Ticker, DataFrame)> {
+        Ticker::iter()
+            .filter(|&x| x != self.ticker)
+            .filter_map(|x| {
+                Some((
+                    x,
+                    Self::read_parquet(x, self.path.as_ref())
+                        .ok()?
+                        .collect()
+                        .ok()?,
+                ))
+            })
+            .collect()
+    }
+
+    // Tickers and ISINs from securities.parquet, or every fund parquet file
+    // before it exists, and data/tickers.json if it exists
+    fn security_master(&self) -> Result<SecurityMaster, Error> {
+        let mut master = SecurityMaster::default();
+        let securities = self.securities_path();
+        if Path::new(&securities).exists() {
+            master.add_securities(&security_master::read_securities(&securities)?);
+        } else {
+            for ticker in Ticker::iter() {
+                if let Ok(df) = Self::read_parquet(ticker, self.path.as_ref())
+                    && let Ok(df) = df.collect()
+                {
+                    // only the US funds publish tickers next to their identifiers
+                    if matches!(
+                        ticker.data_source(),
+                        DataSource::ArkEurope | DataSource::Rize
+                    ) {
+                        master.add_isins(&df)?;
+                    } else {
+                        master.add(&df)?;
+                    }
+                }
+            }
+        }
+
+        let overrides = self.data_dir("tickers.json");
+        if Path::new(&overrides).exists() {
+            master.add_overrides(Path::new(&overrides))?;
+        }
+        Ok(master)
+    }
+
+    pub fn df_format(
+        df: DF,
+        ticker: Option<Ticker>,
+        data_source: Option<DataSource>,
+    ) -> Result<DF, Error> {
+        Self::df_format_with_schema(df, ticker, data_source, *SCHEMA)
+    }
+
+    pub fn df_format_with_schema(
+        df: DF,
+        ticker: Option<Ticker>,
+        data_source: Option<DataSource>,
+        schema: Schema,
+    ) -> Result<DF, Error> {
         let mut df = df.collect()?;
-        if let Some(ds) = data_source {
+        // the values of a download or an old csv are still the source values
+        let fresh = if let Some(ds) = data_source {
             df = format::data_source(ds, df.into())?.collect()?;
+            true
         } else {
-            df = format::df_format_europe_csv(df.into())?.collect()?;
-            df = format::df_format_europe_arkfundsio(df.into())?.collect()?;
-            df = format::df_format_21shares(df.into())?.collect()?;
-            df = format::df_format_arkvx(df.into())?.collect()?;
-            df = format::df_format_europe(df.into())?.collect()?;
-        }
+            let layout = format::Layout::detect(&df)?;
+            df = layout.format(df.into())?.collect()?;
+            layout != format::Layout::NexVeridianJson
+        };
 
         if df.get_column_names().contains(&"market_value_($)") {
             df = df
@@ -206,6 +484,22 @@ impl Ark {
             df = df.select(cols)?;
         }
 
+        // before any rule touches them, a file that already has them keeps them.
+        // Stored parquet and api json are already normalized, their source
+        // values are unknown
+        if schema.raw_columns {
+            for (name, raw) in RAW_COLUMNS {
+                if !df.get_column_names().contains(&raw) {
+                    let value = if fresh {
+                        col(name)
+                    } else {
+                        lit(NULL).cast(DataType::Utf8)
+                    };
+                    df = df.lazy().with_column(value.alias(raw)).collect()?;
+                }
+            }
+        }
+
         if !df.fields().contains(&Field::new("date", DataType::Date)) {
             let date_format =
                 |mut df: DataFrame, format: Option<String>| -> Result<DataFrame, Error> {
@@ -237,7 +531,9 @@ impl Ark {
             }
         }
 
-        df = format::Ticker::all(df.into())?.collect()?;
+        df = format::alias::ALIASES
+            .apply(df.into(), schema.point_in_time)?
+            .collect()?;
 
         let mut expressions: Vec<Expr> = vec![];
 
@@ -310,123 +606,7 @@ impl Ark {
                 .str()
                 .rstrip(None),
         );
-        expressions.push(
-            col("company")
-                .str()
-                .replace_all(lit(" PHARMACEUTICALSP"), lit(""), true)
-                .str()
-                .replace_all(lit(" INTERNATIONAL"), lit(""), true)
-                .str()
-                .replace_all(lit(" GLOBAL"), lit(""), true)
-                .str()
-                .replace_all(lit(" SOFTWARE"), lit(""), true)
-                .str()
-                .replace_all(lit(" Markets"), lit(""), true)
-                .str()
-                .replace_all(lit(" International"), lit(""), true)
-                .str()
-                .replace_all(lit(" AVIATION"), lit(""), true)
-                .str()
-                .replace_all(lit(" COMMUNICATIONS"), lit(""), true)
-                .str()
-                .replace_all(lit(" PHARMACEUTICALS"), lit(""), true)
-                .str()
-                .replace_all(lit(" Therapeutics"), lit(""), true)
-                .str()
-                .replace_all(lit(" THERAPEUTICS"), lit(""), true)
-                .str()
-                .replace_all(lit(" TECHNOLOGIES"), lit(""), true)
-                .str()
-                .replace_all(lit("-A"), lit(""), true)
-                .str()
-                .replace_all(lit("- A"), lit(""), true)
-                .str()
-                .replace_all(lit("CL A"), lit(""), true)
-                .str()
-                .replace_all(lit("CLASS A"), lit(""), true)
-                .str()
-                .replace_all(lit("inc"), lit(""), true)
-                .str()
-                .replace_all(lit("Inc"), lit(""), true)
-                .str()
-                .replace_all(lit("INC"), lit(""), true)
-                .str()
-                .replace_all(lit("incorporated"), lit(""), true)
-                .str()
-                .replace_all(lit("Ltd"), lit(""), true)
-                .str()
-                .replace_all(lit("LTD"), lit(""), true)
-                .str()
-                .replace_all(lit("CORP"), lit(""), true)
-                .str()
-                .replace_all(lit(" CO"), lit(""), true)
-                .str()
-                .replace_all(lit("CORPORATION"), lit(""), true)
-                .str()
-                .replace_all(lit("Corporation"), lit(""), true)
-                .str()
-                .replace_all(lit("- C"), lit(""), true)
-                .str()
-                .replace_all(lit("-"), lit(""), true)
-                .str()
-                .replace_all(lit(","), lit(""), true)
-                .str()
-                .replace_all(lit("."), lit(""), true)
-                .str()
-                .replace_all(lit(" &CURITY"), lit(""), true)
-                .str()
-                .replace_all(lit(" &"), lit(""), true)
-                .str()
-                .replace(lit("HLDGS"), lit(""), true)
-                .str()
-                .replace(lit("HOLDINGS"), lit(""), true)
-                .str()
-                .replace(lit("Holdings"), lit(""), true)
-                .str()
-                .replace(lit(" HOLDIN"), lit(""), true)
-                .str()
-                .replace(lit("ORATION"), lit(""), true)
-                .str()
-                .replace(lit(" PLC"), lit(""), true)
-                .str()
-                .replace(lit(" AG"), lit(""), true)
-                .str()
-                .replace(lit(" ADR"), lit(""), true)
-                .str()
-                .replace(lit("DR"), lit(""), true)
-                .str()
-                .replace(lit(" SA"), lit(""), true)
-                .str()
-                .replace(lit(" NV"), lit(""), true)
-                .str()
-                .replace(lit(" SE"), lit(""), true)
-                .str()
-                .replace(lit(" CL C"), lit(""), true)
-                .str()
-                .replace(lit("COINBASE GLOBAL"), lit("COINBASE"), true)
-                .str()
-                .replace(lit("Coinbase Global"), lit("Coinbase"), true)
-                .str()
-                .replace(lit("Blackdaemon"), lit("Blockdaemon"), true)
-                .str()
-                .replace(lit("DISCOVERY"), lit("Dassault Systemes"), true)
-                .str()
-                .replace(lit("Space Investment"), lit("SpaceX"), true)
-                .str()
-                .replace(
-                    lit("Space Exploration Technologies Corp"),
-                    lit("SpaceX"),
-                    true,
-                )
-                .str()
-                .replace(
-                    lit("Space Exploration Technologies Co"),
-                    lit("SpaceX"),
-                    true,
-                )
-                .str()
-                .rstrip(None),
-        );
+        expressions.push(format::company::COMPANY_RULES.expr(ticker, col("company")));
 
         df = df
             .lazy()
@@ -475,100 +655,417 @@ impl Ark {
 
         df = df.lazy().with_columns(expressions).collect()?;
 
-        if df.get_column_names().contains(&"share_price") {
-            df = df.select([
-                "date",
-                "ticker",
-                "cusip",
-                "company",
-                "market_value",
-                "shares",
-                "share_price",
-                "weight",
-            ])?;
-        } else if !df
-            .get_column_names()
-            .eq(&["date", "ticker", "cusip", "company", "weight"])
-        {
-            df = df.select(["date", "ticker", "cusip", "company", "weight"])?;
+        if schema.id_columns {
+            df = df
+                .lazy()
+                .with_columns([
+                    identifier::id_type_expr(col("cusip")).alias("id_type"),
+                    // the country of a CUSIP is looked up by Ark::format
+                    identifier::isin_expr(col("cusip"), HashMap::new()).alias("isin"),
+                ])
+                .collect()?;
+        }
+
+        if schema.asset_class {
+            let money_market = df
+                .get_column_names()
+                .contains(&"money_market")
+                .then_some("money_market");
+            // the flag columns are gone once stored, a stored class is kept
+            let mut class = asset_class::AssetClass::expr(money_market);
+            if df.get_column_names().contains(&"asset_class") {
+                class = col("asset_class").fill_null(class);
+            }
+            df = df
+                .lazy()
+                .with_column(class.alias("asset_class"))
+                .collect()?;
+        }
+
+        let columns = schema.columns(df.get_column_names().contains(&"share_price"));
+        if df.get_column_names() != columns {
+            df = df.select(columns)?;
         }
 
         Ok(df.into())
     }
 
-    pub fn get_api(
+    pub async fn get_api(
         &self,
-        last_day: Option<NaiveDate>,
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
         source: Option<&Source>,
     ) -> Result<DataFrame, Error> {
-        let default_start_day = "2000-01-01";
-        let url = match (self.ticker.data_source(), last_day, source) {
-            (DataSource::ArkEurope, Some(last_day), _) => format!(
-                "https://api.nexveridian.com/ark_holdings?ticker={}&start={}",
-                self.ticker, last_day
-            ),
-            (DataSource::ArkEurope, None, _) => format!(
-                "https://api.nexveridian.com/ark_holdings?ticker={}&start={}",
-                self.ticker, default_start_day
-            ),
+        let default_start_day = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let arkfunds_io = self.ticker.data_source() != DataSource::ArkEurope
+            && matches!(
+                source,
+                Some(Source::ArkFundsIoIncremental | Source::ArkFundsIoFull)
+            );
 
-            // arkfunds.io
-            (_, Some(last_day), Some(Source::ArkFundsIoIncremental)) => format!(
-                "https://arkfunds.io/api/v2/etf/holdings?symbol={}&date_from={}",
-                self.ticker, last_day
-            ),
-            (_, None, Some(Source::ArkFundsIoIncremental))
-            | (_, _, Some(Source::ArkFundsIoFull)) => format!(
-                "https://arkfunds.io/api/v2/etf/holdings?symbol={}&date_from={}",
-                self.ticker, default_start_day
-            ),
-            // api.nexveridian.com
-            (_, Some(last_day), _) => format!(
-                "https://api.nexveridian.com/ark_holdings?ticker={}&start={}",
-                self.ticker, last_day
-            ),
-            (_, None, _) => format!(
-                "https://api.nexveridian.com/ark_holdings?ticker={}&start={}",
-                self.ticker, default_start_day
-            ),
+        let url = if arkfunds_io {
+            let start = match (source, end) {
+                // the unbounded full history always starts at the beginning
+                (Some(Source::ArkFundsIoFull), None) => default_start_day,
+                _ => start.unwrap_or(default_start_day),
+            };
+            let end = end.map_or_else(String::new, |end| format!("&date_to={end}"));
+            format!(
+                "{}/etf/holdings?symbol={}&date_from={start}{end}",
+                UPSTREAM.arkfunds_io, self.ticker
+            )
+        } else {
+            let end = end.map_or_else(String::new, |end| format!("&end={end}"));
+            format!(
+                "{}/ark_holdings?ticker={}&start={}{end}",
+                UPSTREAM.api,
+                self.ticker,
+                start.unwrap_or(default_start_day)
+            )
+        };
+
+        let response = Reader::fetch(self.transport.as_ref(), &Request::new(&url)).await?;
+        Reader::Json.check(&url, &response, None)?;
+        let parsed = Reader::Json.parse(response.body.clone()).and_then(|df| {
+            if arkfunds_io && !df.is_empty() {
+                Self::unnest_holdings(&df)
+            } else {
+                Ok(df)
+            }
+        });
+        // a payload that fails to parse is archived too
+        self.archive_raw("json", &response.body, parsed.as_ref().ok())?;
+        parsed
+    }
+
+    /// Fetches `start..=end` from the api one quarter at a time. The parquet
+    /// file is written after every quarter and the last finished quarter is
+    /// kept in `data/cache/{ticker}.backfill`, so an interrupted backfill of
+    /// the same window and source continues where it stopped.
+    pub async fn backfill(
+        mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+        source: Source,
+    ) -> Result<Self, Error> {
+        let checkpoint = format!("{}/{}.backfill", self.data_dir("cache"), self.ticker);
+        let window = json!({
+            "start": start.to_string(),
+            "end": end.to_string(),
+            "source": format!("{source:?}"),
+        });
+        // a checkpoint of another window or source is started over
+        let mut chunk_start = std::fs::read(&checkpoint)
+            .ok()
+            .and_then(|x| serde_json::from_slice::<Value>(&x).ok())
+            .filter(|x| {
+                ["start", "end", "source"]
+                    .iter()
+                    .all(|&k| x[k] == window[k])
+            })
+            .and_then(|x| NaiveDate::from_str(x["done"].as_str()?).ok())
+            .map_or(start, |done| (done + Duration::days(1)).max(start));
+        let mut existing_file = Self::read_parquet(self.ticker, self.path.as_ref()).is_ok();
+
+        while chunk_start <= end {
+            let chunk_end =
+                (chunk_start + Months::new(BACKFILL_CHUNK_MONTHS) - Duration::days(1)).min(end);
+            println!("{}: backfilling {chunk_start} to {chunk_end}", self.ticker);
+
+            let update = self
+                .get_api(Some(chunk_start), Some(chunk_end), Some(&source))
+                .await?;
+            if update.height() > 0 {
+                self.df = if existing_file {
+                    Self::concat_df(vec![
+                        Self::df_format(self.df, Some(self.ticker), None)?,
+                        Self::df_format(update.into(), Some(self.ticker), None)?,
+                    ])?
+                } else {
+                    Self::df_format(update.into(), Some(self.ticker), None)?
+                };
+                // same path as a daily update, so the securities table and
+                // schema columns follow the backfilled rows
+                self = self.sort()?.write_parquet()?;
+                self.df = self.df.collect()?.into();
+                existing_file = true;
+            }
+
+            create_dir_all(self.data_dir("cache"))?;
+            let mut done = window.clone();
+            done["done"] = json!(chunk_end.to_string());
+            std::fs::write(&checkpoint, serde_json::to_vec_pretty(&done)?)?;
+            chunk_start = chunk_end + Duration::days(1);
+        }
+
+        if Path::new(&checkpoint).exists() {
+            std::fs::remove_file(&checkpoint)?;
+        }
+        Ok(self)
+    }
+
+    // Trading days with no rows between the first and last date, as windows
+    pub fn gaps(&self) -> Result<Vec<(NaiveDate, NaiveDate)>, Error> {
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let dates: Vec<_> = self
+            .df
+            .clone()
+            .collect()?
+            .column("date")?
+            .date()?
+            .into_iter()
+            .flatten()
+            .map(|x| epoch + Duration::days(x.into()))
+            .collect();
+        Ok(gaps::windows(&gaps::missing_days(&dates)))
+    }
+
+    /// Compares the parquet history with itself formatted again, and with the
+    /// raw archive of the same days formatted from scratch
+    pub fn company_changes(&self) -> Result<CompanyChanges, Error> {
+        let stored = self.df.clone().collect()?;
+        let formatted =
+            Self::df_format(stored.clone().into(), Some(self.ticker), None)?.collect()?;
+
+        let split = if Self::has_archive(self.ticker, self.path.as_deref()) {
+            let rebuilt = Self::rebuild_from_archive(self.ticker, self.path.clone())?.collect()?;
+            Self::changed_companies(&formatted, &rebuilt)?
+        } else {
+            vec![]
         };
+        Ok(CompanyChanges {
+            rewritten: Self::changed_companies(&stored, &formatted)?,
+            split,
+        })
+    }
 
-        let mut df = Reader::Json.get_data_url(url)?;
-        df = match source {
-            Some(Source::ArkFundsIoIncremental | Source::ArkFundsIoFull) => df
-                .column("holdings")?
-                .clone()
-                .explode()?
-                .struct_()?
-                .clone()
-                .unnest(),
-            _ => df,
+    fn changed_companies(
+        before: &DataFrame,
+        after: &DataFrame,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let names = |df: &DataFrame| {
+            df.clone()
+                .lazy()
+                .select([col("date"), col("cusip"), col("company")])
+                .filter(col("cusip").is_not_null())
         };
-        Ok(df)
+        let keys = [col("date"), col("cusip")];
+        let df = names(before)
+            .join(
+                names(after),
+                keys.clone(),
+                keys,
+                JoinArgs::new(JoinType::Inner),
+            )
+            .filter(col("company").neq(col("company_right")))
+            .select([col("company"), col("company_right")])
+            .unique_stable(None, UniqueKeepStrategy::First)
+            .sort("company", SortOptions::default())
+            .collect()?;
+
+        Ok(df
+            .column("company")?
+            .utf8()?
+            .into_iter()
+            .zip(df.column("company_right")?.utf8()?)
+            .filter_map(|(before, after)| Some((before?.to_owned(), after?.to_owned())))
+            .collect())
+    }
+
+    // Every distinct company name in the parquet history
+    pub fn companies(&self) -> Result<Vec<String>, Error> {
+        let df = self
+            .df
+            .clone()
+            .collect()?
+            .lazy()
+            .select([col("company").unique().sort(false)])
+            .collect()?;
+        Ok(df
+            .column("company")?
+            .utf8()?
+            .into_iter()
+            .flatten()
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    // Fetches only the missing windows found by Ark::gaps
+    pub async fn fill_gaps(&mut self, source: Source) -> Result<(), Error> {
+        let mut dfs = vec![Self::df_format(self.df.clone(), Some(self.ticker), None)?];
+        for (start, end) in self.gaps()? {
+            println!("{}: filling gap {start} to {end}", self.ticker);
+            match self.get_api(Some(start), Some(end), Some(&source)).await {
+                Ok(update) if update.height() > 0 => {
+                    dfs.push(Self::df_format(update.into(), Some(self.ticker), None)?);
+                }
+                Ok(_) => println!("{}: no data from {start} to {end}", self.ticker),
+                Err(e) => eprintln!("{}: failed to fill {start} to {end}, {e}", self.ticker),
+            }
+        }
+
+        self.df = Self::concat_df(dfs)?.collect()?.into();
+        Ok(())
+    }
+
+    // arkfunds.io nests every holding in a list of structs
+    fn unnest_holdings(df: &DataFrame) -> Result<DataFrame, Error> {
+        Ok(df
+            .column("holdings")?
+            .clone()
+            .explode()?
+            .struct_()?
+            .clone()
+            .unnest())
     }
 
-    pub fn get_csv_ark(&self) -> Result<DataFrame, Error> {
+    pub async fn get_csv_ark(&mut self) -> Result<DataFrame, Error> {
         let url = self.ticker.get_url();
-        Reader::Csv.get_data_url(url)
+        let cache = HttpCache::new(self.data_dir("cache"));
+
+        // without a parquet file there is nothing to skip to, always download
+        let request = if Self::read_parquet(self.ticker, self.path.as_ref()).is_ok() {
+            cache.request(&url)?
+        } else {
+            Request::new(&url)
+        };
+
+        let response = Reader::fetch(self.transport.as_ref(), &request).await?;
+        let data_source = self.ticker.data_source();
+        // the Europe and Rize downloads are spreadsheets
+        let reader = match data_source {
+            DataSource::ArkEurope | DataSource::Rize if Reader::is_xlsx(&response) => Reader::Xlsx,
+            _ => Reader::Csv,
+        };
+        reader.check(&url, &response, Some(data_source))?;
+        let parsed = reader.parse(response.body.clone());
+        self.archive_raw(reader.extension(), &response.body, parsed.as_ref().ok())?;
+        let df = parsed?;
+        self.validators = Some((
+            url,
+            Response {
+                body: vec![],
+                ..response
+            },
+        ));
+        Ok(df)
+    }
+
+    // Named by the as-of date of the holdings, today if the payload has none
+    fn archive_raw(
+        &self,
+        extension: &str,
+        body: &[u8],
+        df: Option<&DataFrame>,
+    ) -> Result<(), Error> {
+        let as_of = df
+            .and_then(Self::as_of_date)
+            .unwrap_or_else(|| Local::now().date_naive());
+        Archive::new(self.data_dir("raw")).save(self.ticker, as_of, extension, body)?;
+        Ok(())
+    }
+
+    // Last date of a parsed download, the date column of ARK, 21Shares and the
+    // api, or the title rows of the Europe csv
+    fn as_of_date(df: &DataFrame) -> Option<NaiveDate> {
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let Some(date) = ["date", "Date"].into_iter().find_map(|x| df.column(x).ok()) else {
+            return format::title_date(df).ok();
+        };
+
+        if let Ok(dates) = date.date() {
+            return dates.max().map(|x| epoch + Duration::days(x.into()));
+        }
+        date.utf8()
+            .ok()?
+            .into_iter()
+            .flatten()
+            .filter_map(|x| {
+                ["%m/%d/%Y", "%Y-%m-%d", "%Y/%m/%d"]
+                    .into_iter()
+                    .find_map(|format| NaiveDate::parse_from_str(x.trim(), format).ok())
+            })
+            .max()
     }
 
     pub fn merge_old_csv_to_parquet(ticker: Ticker, path: Option<String>) -> Result<Self, Error> {
+        let csv = Self::data_dir_in(path.as_deref(), "csv");
         let mut dfs = vec![];
-        for x in glob(&format!("data/csv/{ticker}/*"))?.filter_map(Result::ok) {
+        for x in glob(&format!("{csv}/{ticker}/*"))?.filter_map(Result::ok) {
             dfs.push(LazyCsvReader::new(x).finish()?);
         }
 
-        let mut df = concat(dfs, UnionArgs::default())?.into();
+        // old csv files predate the current schemas, detect their layout
+        let mut df = Self::df_format(
+            concat(dfs, UnionArgs::default())?.into(),
+            Some(ticker),
+            None,
+        )?;
 
         if Self::read_parquet(ticker, path.as_ref()).is_ok() {
             let df_old = Self::read_parquet(ticker, path.as_ref())?;
-            df = Self::concat_df(vec![
-                Self::df_format(df_old, None)?,
-                Self::df_format(df, None)?,
-            ])?;
-            df = Self::df_format(df, None)?;
+            df = Self::concat_df(vec![Self::df_format(df_old, Some(ticker), None)?, df])?;
+            df = Self::df_format(df, Some(ticker), None)?;
         }
-        Ok(Self { df, ticker, path })
+        Ok(Self {
+            df,
+            ticker,
+            path,
+            transport: Self::default_transport()?,
+            unchanged: false,
+            source: None,
+            validators: None,
+        })
+    }
+
+    /// Rebuilds the history of a ticker from scratch, from every payload in
+    /// `data/raw/{ticker}` and every old csv in `data/csv/{ticker}`, using the
+    /// current formatting rules
+    pub fn rebuild_from_archive(ticker: Ticker, path: Option<String>) -> Result<Self, Error> {
+        let mut ark = Self {
+            df: DF::DataFrame(Box::new(DataFrame::empty())),
+            ticker,
+            path,
+            transport: Self::default_transport()?,
+            unchanged: false,
+            source: None,
+            validators: None,
+        };
+
+        let mut files = Archive::new(ark.data_dir("raw")).files(ticker)?;
+        files.extend(glob(&format!("{}/{ticker}/*", ark.data_dir("csv")))?.filter_map(Result::ok));
+
+        let mut dfs = vec![];
+        for file in files {
+            let body = std::fs::read(&file)?;
+            let df = match file.extension().and_then(|x| x.to_str()) {
+                Some("json") => {
+                    let df = Reader::Json.parse(body)?;
+                    if df.get_column_names().contains(&"holdings") {
+                        Self::unnest_holdings(&df)?
+                    } else {
+                        df
+                    }
+                }
+                Some("xlsx") => Reader::Xlsx.parse(body)?,
+                _ => Reader::Csv.parse(body)?,
+            };
+            // api answers for days before the fund existed or without holdings
+            if df.height() == 0 {
+                continue;
+            }
+            dfs.push(
+                Self::df_format(df.into(), Some(ticker), None)
+                    .map_err(|e| anyhow!("Failed to format {}: {e}", file.display()))?,
+            );
+        }
+
+        if dfs.is_empty() {
+            return Err(anyhow!("No archived files for {ticker}"));
+        }
+
+        ark.df = Self::concat_df(dfs)?;
+        Ok(ark)
     }
 }
 
@@ -577,24 +1074,30 @@ mod tests {
     use std::fs;
 
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
     use super::*;
-    use crate::test_utils::*;
+    use crate::{schema::COLUMNS, test_utils::*};
 
-    #[test]
-    fn read_write_parquet() -> Result<(), Error> {
+    #[tokio::test]
+    async fn read_write_parquet() -> Result<(), Error> {
         let test_df = defualt_df(&[Some("COIN")], &[Some("COINBASE")])?;
 
-        Ark::write_df_parquet("data/test/ARKK.parquet", test_df.clone().into())?;
-        let read = Ark::new(Source::Read, Ticker::ARKK, Some("data/test".to_owned()))?.collect()?;
-        fs::remove_file("data/test/ARKK.parquet")?;
+        let dir = TestDir::new("read_write_parquet")?;
+        Ark::write_df_parquet(
+            &format!("{}/ARKK.parquet", dir.path()),
+            test_df.clone().into(),
+        )?;
+        let read = Ark::new(Source::Read, Ticker::ARKK, Some(dir.path().to_owned()))
+            .await?
+            .collect()?;
 
         assert_eq!(read, test_df);
         Ok(())
     }
 
-    #[test]
-    fn arkw_format_arkb() -> Result<(), Error> {
+    #[tokio::test]
+    async fn arkw_format_arkb() -> Result<(), Error> {
         let test_df = defualt_df(
             &[None::<&str>, Some("ARKB"), Some("ARKB")],
             &[
@@ -604,11 +1107,13 @@ mod tests {
             ],
         )?;
 
-        Ark::write_df_parquet("data/test/ARKW.parquet", test_df.into())?;
-        let read = Ark::new(Source::Read, Ticker::ARKW, Some("data/test".to_owned()))?.collect()?;
-        fs::remove_file("data/test/ARKW.parquet")?;
+        let dir = TestDir::new("arkw_format_arkb")?;
+        Ark::write_df_parquet(&format!("{}/ARKW.parquet", dir.path()), test_df.into())?;
+        let read = Ark::new(Source::Read, Ticker::ARKW, Some(dir.path().to_owned()))
+            .await?
+            .collect()?;
 
-        let df = Ark::df_format(read.into(), None)?.collect()?;
+        let df = Ark::df_format(read.into(), None, None)?.collect()?;
         assert_eq!(
             df,
             defualt_df(
@@ -620,8 +1125,8 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn arkf_format_arkb() -> Result<(), Error> {
+    #[tokio::test]
+    async fn arkf_format_arkb() -> Result<(), Error> {
         let test_df = defualt_df(
             &[None::<&str>, Some("ARKB"), Some("ARKB")],
             &[
@@ -630,11 +1135,13 @@ mod tests {
                 Some("ARKB"),
             ],
         )?;
-        Ark::write_df_parquet("data/test/ARKF.parquet", test_df.into())?;
-        let read = Ark::new(Source::Read, Ticker::ARKF, Some("data/test".to_owned()))?.collect()?;
-        fs::remove_file("data/test/ARKF.parquet")?;
+        let dir = TestDir::new("arkf_format_arkb")?;
+        Ark::write_df_parquet(&format!("{}/ARKF.parquet", dir.path()), test_df.into())?;
+        let read = Ark::new(Source::Read, Ticker::ARKF, Some(dir.path().to_owned()))
+            .await?
+            .collect()?;
 
-        let df = Ark::df_format(read.into(), None)?.collect()?;
+        let df = Ark::df_format(read.into(), None, None)?.collect()?;
         assert_eq!(
             df,
             defualt_df(
@@ -645,4 +1152,557 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn new_from_fixture() -> Result<(), Error> {
+        let dir = TestDir::new("fixtures_ark")?;
+        dir.fixture(&Ticker::ARKK.get_url(), TESLA_CSV)?;
+
+        let df = Ark::new_with_transport(
+            Source::Ark,
+            Ticker::ARKK,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?
+        .collect()?;
+        let archived = Archive::new(format!("{}/raw", dir.path())).files(Ticker::ARKK)?;
+
+        // archived under the date of the holdings, not the day of the download
+        assert_eq!(archived.len(), 1);
+        assert!(
+            archived[0]
+                .to_string_lossy()
+                .contains("raw/ARKK/2026-10-16-")
+        );
+        assert_eq!(
+            df,
+            df![
+                "date" => [NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()],
+                "ticker" => ["TSLA"],
+                "cusip" => ["88160R101"],
+                "company" => ["TESLA"],
+                "market_value" => [250_000_i64],
+                "shares" => [1_000_i64],
+                "share_price" => [250.00],
+                "weight" => [10.50],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn raw_columns() -> Result<(), Error> {
+        let schema = Schema {
+            raw_columns: true,
+            ..Default::default()
+        };
+        let df = df![
+            "date" => ["10/16/2026"],
+            "fund" => ["ARKK"],
+            "company" => ["TESLA INC"],
+            "ticker" => ["TSLA UW"],
+            "cusip" => ["88160R101"],
+            "shares" => ["1,000"],
+            "market value ($)" => ["$250,000.00"],
+            "weight (%)" => ["10.50%"],
+        ]?;
+
+        let df = Ark::df_format_with_schema(
+            df.into(),
+            Some(Ticker::ARKK),
+            Some(DataSource::Ark),
+            schema,
+        )?;
+        // formatting it again, like the parquet file on the next run, keeps
+        // the values from the source file
+        let df = Ark::df_format_with_schema(df, Some(Ticker::ARKK), None, schema)?.collect()?;
+
+        assert_eq!(
+            df.get_column_names(),
+            [COLUMNS.as_slice(), &["company_raw", "ticker_raw"]].concat()
+        );
+        assert_eq!(
+            df.select(["ticker", "company", "ticker_raw", "company_raw"])?,
+            df![
+                "ticker" => ["TSLA"],
+                "company" => ["TESLA"],
+                "ticker_raw" => ["TSLA UW"],
+                "company_raw" => ["TESLA INC"],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn raw_columns_of_existing_parquet() -> Result<(), Error> {
+        let schema = Schema {
+            raw_columns: true,
+            ..Default::default()
+        };
+
+        // turned on over a parquet file written without them
+        let df = Ark::df_format_with_schema(
+            defualt_df(&[], &[])?.into(),
+            Some(Ticker::ARKK),
+            None,
+            schema,
+        )?
+        .collect()?;
+
+        assert_eq!(
+            df.select(["ticker_raw", "company_raw"])?,
+            df![
+                "ticker_raw" => [None::<&str>],
+                "company_raw" => [None::<&str>],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn id_columns() -> Result<(), Error> {
+        let schema = Schema {
+            id_columns: true,
+            ..Default::default()
+        };
+        let test_df = df![
+            "date" => ["2024-01-01"; 3],
+            "ticker" => ["TSLA"; 3],
+            "cusip" => ["88160R101", "US88160R1014", "TESLA"],
+            "company" => ["TESLA"; 3],
+            "market_value" => [10; 3],
+            "shares" => [10; 3],
+            "share_price" => [100.00; 3],
+            "weight" => [10.00; 3],
+        ]?;
+
+        let df = Ark::df_format_with_schema(test_df.into(), None, None, schema)?.collect()?;
+        assert_eq!(
+            df.select(["id_type", "isin"])?,
+            df![
+                "id_type" => ["cusip", "isin", "unknown"],
+                // a CUSIP no fund published an ISIN for is taken as US
+                "isin" => [Some("US88160R1014"), Some("US88160R1014"), None],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn asset_class_kept() -> Result<(), Error> {
+        let schema = Schema {
+            asset_class: true,
+            ..Default::default()
+        };
+        let df = df![
+            "Account" => ["ARKA"],
+            "Date" => ["10/16/2026"],
+            "StockTicker" => ["MMF"],
+            "CUSIP" => ["X"],
+            "SecurityName" => ["DREYFUS GOVT"],
+            "Shares" => ["1"],
+            "Price" => ["1"],
+            "MarketValue" => ["1"],
+            "Weightings" => ["1"],
+            "MoneyMarketFlag" => ["Y"],
+        ]?;
+
+        let df = Ark::df_format_with_schema(
+            df.into(),
+            Some(Ticker::ARKA),
+            Some(DataSource::Shares21),
+            schema,
+        )?;
+        // the stored parquet has no MoneyMarketFlag on the next run
+        let df = Ark::df_format_with_schema(df, Some(Ticker::ARKA), None, schema)?.collect()?;
+
+        assert_eq!(df.column("asset_class")?.utf8()?.get(0), Some("cash"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn new_from_europe_xlsx() -> Result<(), Error> {
+        let dir = TestDir::new("fixtures_europe")?;
+        dir.fixture(
+            &Ticker::EUROPE_ARKI.get_url(),
+            xlsx(&[
+                &["ARK Artificial Intelligence & Robotics UCITS ETF"],
+                &["Holdings as of 16/10/2026"],
+                &["name", "ISIN", "Currency", "Weight"],
+                &["NVIDIA CORP", "US67066G1040", "USD", "5.12"],
+            ])?,
+        )?;
+
+        let df = Ark::new_with_transport(
+            Source::Ark,
+            Ticker::EUROPE_ARKI,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?
+        .collect()?;
+
+        assert_eq!(
+            df,
+            df![
+                "date" => [NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()],
+                "ticker" => [None::<&str>],
+                "cusip" => ["US67066G1040"],
+                "company" => ["NVIDIA"],
+                "market_value" => [None::<i64>],
+                "shares" => [None::<i64>],
+                "share_price" => [None::<f64>],
+                "weight" => [5.12],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_modified_skips_write() -> Result<(), Error> {
+        let dir = TestDir::new("not_modified")?;
+        let test_df = defualt_df(&[Some("COIN")], &[Some("COINBASE")])?;
+        Ark::write_df_parquet(
+            &format!("{}/ARKK.parquet", dir.path()),
+            test_df.clone().into(),
+        )?;
+
+        let url = Ticker::ARKK.get_url();
+        dir.fixture(&url, "")?;
+        dir.fixture_meta(&url, r#"{"status": 304}"#)?;
+
+        let ark = Ark::new_with_transport(
+            Source::Ark,
+            Ticker::ARKK,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?;
+        let unchanged = ark.is_unchanged();
+        let df = ark.write_parquet()?.collect()?;
+
+        assert!(unchanged);
+        assert_eq!(df, test_df);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validators_cached_after_write() -> Result<(), Error> {
+        let dir = TestDir::new("validators")?;
+        let url = Ticker::ARKK.get_url();
+        dir.fixture(&url, TESLA_CSV)?;
+        dir.fixture_meta(&url, r#"{"status": 200, "headers": {"etag": "\"abc\""}}"#)?;
+
+        let ark = Ark::new_with_transport(
+            Source::Ark,
+            Ticker::ARKK,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?;
+        let cache = HttpCache::new(format!("{}/cache", dir.path()));
+        let before = cache.request(&url)?;
+        ark.write_parquet()?;
+        let after = cache.request(&url)?;
+
+        // a download that fails to format or write is fetched again
+        assert_eq!(before, Request::new(&url));
+        assert_eq!(after, Request::new(&url).header("If-None-Match", "\"abc\""));
+        Ok(())
+    }
+
+    #[test]
+    fn europe_tickers_from_us_funds() -> Result<(), Error> {
+        let dir = TestDir::new("europe_tickers")?;
+        let us = df![
+            "date" => [NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()],
+            "ticker" => ["NVDA"],
+            "cusip" => ["67066G104"],
+            "company" => ["NVIDIA"],
+            "weight" => [5.0],
+        ]?;
+        Ark::write_df_parquet(&format!("{}/ARKK.parquet", dir.path()), us.into())?;
+        let europe = df![
+            "date" => [NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(); 2],
+            "ticker" => [None::<&str>, None],
+            "cusip" => ["US67066G1040", "DE0007164600"],
+            "company" => ["NVIDIA", "SAP"],
+            "weight" => [5.12, 3.0],
+        ]?;
+        Ark::write_df_parquet(
+            &format!("{}/EUROPE_ARKI.parquet", dir.path()),
+            europe.into(),
+        )?;
+
+        let ark = Ark::open(
+            Ticker::EUROPE_ARKI,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )?
+        .format()?;
+
+        assert_eq!(
+            ark.collect()?
+                .column("ticker")?
+                .utf8()?
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [Some("NVDA"), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn securities_seeded_from_every_fund() -> Result<(), Error> {
+        let dir = TestDir::new("securities_seed")?;
+        let us = df![
+            "date" => [NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()],
+            "ticker" => ["NVDA"],
+            "cusip" => ["67066G104"],
+            "company" => ["NVIDIA"],
+            "weight" => [5.0],
+        ]?;
+        Ark::write_df_parquet(&format!("{}/ARKW.parquet", dir.path()), us.into())?;
+        Ark::write_df_parquet(
+            &format!("{}/ARKK.parquet", dir.path()),
+            defualt_df(&[], &[])?.into(),
+        )?;
+
+        // the first fund written after a deploy
+        Ark::open(Ticker::ARKK, Some(dir.path().to_owned()), dir.transport())?.write_parquet()?;
+        let table =
+            security_master::read_securities(&format!("{}/securities.parquet", dir.path()))?;
+
+        assert_eq!(
+            table["67066G104"].funds.iter().collect::<Vec<_>>(),
+            ["ARKW"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rebuild_from_archive() -> Result<(), Error> {
+        let dir = TestDir::new("rebuild")?;
+        let archive = Archive::new(format!("{}/raw", dir.path()));
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        archive.save(
+            Ticker::ARKK,
+            day(15),
+            "json",
+            tesla_json(&["2026-10-15"]).as_bytes(),
+        )?;
+        archive.save(Ticker::ARKK, day(16), "csv", TESLA_CSV.as_bytes())?;
+        archive.save(Ticker::ARKK, day(14), "json", b"[]")?;
+        // same payload downloaded twice is only stored once
+        archive.save(Ticker::ARKK, day(17), "csv", TESLA_CSV.as_bytes())?;
+        // old csv of the same path
+        fs::create_dir_all(format!("{}/csv/ARKK", dir.path()))?;
+        fs::write(
+            format!("{}/csv/ARKK/2026-10-13.csv", dir.path()),
+            TESLA_CSV.replace("10/16/2026", "10/13/2026"),
+        )?;
+
+        let df = Ark::rebuild_from_archive(Ticker::ARKK, Some(dir.path().to_owned()))?
+            .sort()?
+            .collect()?;
+
+        assert_eq!(
+            df,
+            df![
+                "date" => [day(13), day(15), day(16)],
+                "ticker" => ["TSLA", "TSLA", "TSLA"],
+                "cusip" => ["88160R101", "88160R101", "88160R101"],
+                "company" => ["TESLA", "TESLA", "TESLA"],
+                "market_value" => [250_000_i64, 200_000, 250_000],
+                "shares" => [1_000_i64, 1_000, 1_000],
+                "share_price" => [250.00, 200.00, 250.00],
+                "weight" => [10.50, 10.50, 10.50],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn company_changes() -> Result<(), Error> {
+        let dir = TestDir::new("company_changes")?;
+        let day = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let stored = df![
+            "date" => [day; 2],
+            "ticker" => ["TSLA", "TSM"],
+            "cusip" => ["88160R101", "874039100"],
+            "company" => ["TESLA MOTORS", "TMSC"],
+            "weight" => [10.5, 2.0],
+        ]?;
+        Ark::write_df_parquet(&format!("{}/ARKK.parquet", dir.path()), stored.into())?;
+        Archive::new(format!("{}/raw", dir.path())).save(
+            Ticker::ARKK,
+            day,
+            "csv",
+            TESLA_CSV.as_bytes(),
+        )?;
+
+        let changes = Ark::open(Ticker::ARKK, Some(dir.path().to_owned()), dir.transport())?
+            .company_changes()?;
+
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+        assert_eq!(
+            changes,
+            CompanyChanges {
+                rewritten: vec![pair("TMSC", "TAIWAN SEMICONDUCTOR")],
+                split: vec![pair("TESLA MOTORS", "TESLA")],
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback_to_api() -> Result<(), Error> {
+        let dir = TestDir::new("fallback")?;
+        let existing = Reader::Json.parse(
+            br#"[{"company":"COINBASE","cusip":"19260Q107","date":"2026-10-14","market_value":100000,"share_price":100.0,"shares":1000,"ticker":"COIN","weight":5.0,"weight_rank":2}]"#.to_vec(),
+        )?;
+        Ark::write_df_parquet(
+            &format!("{}/ARKK.parquet", dir.path()),
+            Ark::df_format(existing.into(), None, None)?,
+        )?;
+        dir.fixture(
+            &format!("{}/ark_holdings?ticker=ARKK&start=2026-10-14", UPSTREAM.api),
+            tesla_json(&["2026-10-15"]),
+        )?;
+
+        // no fixture for the ARK csv, answered with a 404
+        let ark = Ark::new_with_fallback(
+            &Source::parse_chain("Ark, ApiIncremental")?,
+            Ticker::ARKK,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?;
+
+        assert_eq!(ark.source(), Some(Source::ApiIncremental));
+        assert_eq!(ark.collect()?.height(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback_from_read() -> Result<(), Error> {
+        let dir = TestDir::new("fallback_read")?;
+        dir.fixture(
+            &format!("{}/ark_holdings?ticker=ARKK&start=2000-01-01", UPSTREAM.api),
+            tesla_json(&["2026-10-15"]),
+        )?;
+
+        // no parquet file to read
+        let ark = Ark::new_with_fallback(
+            &Source::parse_chain("Read, ApiFull")?,
+            Ticker::ARKK,
+            Some(dir.path().to_owned()),
+            dir.transport(),
+        )
+        .await?;
+
+        assert_eq!(ark.source(), Some(Source::ApiFull));
+        assert_eq!(ark.collect()?.height(), 1);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::resumes("2026-04-01", "ApiFull", 1)]
+    // the checkpoint of another window or source is started over
+    #[case::other_window("2026-01-01", "ApiFull", 2)]
+    #[case::other_source("2026-04-01", "ArkFundsIoFull", 2)]
+    #[tokio::test]
+    async fn backfill_checkpoint(
+        #[case] checkpoint_start: &str,
+        #[case] checkpoint_source: &str,
+        #[case] expected: usize,
+    ) -> Result<(), Error> {
+        let dir = TestDir::new(&format!("backfill_{checkpoint_source}_{checkpoint_start}"))?;
+        let day = |m, d| NaiveDate::from_ymd_opt(2026, m, d).unwrap();
+        let url = |start, end| {
+            format!(
+                "{}/ark_holdings?ticker=ARKK&start={start}&end={end}",
+                UPSTREAM.api
+            )
+        };
+
+        let checkpoint = format!("{}/cache/ARKK.backfill", dir.path());
+        fs::create_dir_all(format!("{}/cache", dir.path()))?;
+        fs::write(
+            &checkpoint,
+            format!(
+                r#"{{"start": "{checkpoint_start}", "end": "2026-10-16", "source": "{checkpoint_source}", "done": "2026-06-30"}}"#
+            ),
+        )?;
+        dir.fixture(&url(day(4, 1), day(6, 30)), tesla_json(&["2026-06-30"]))?;
+        dir.fixture(&url(day(7, 1), day(9, 30)), tesla_json(&["2026-09-30"]))?;
+        dir.fixture(&url(day(10, 1), day(10, 16)), "[]")?;
+
+        Ark::open(Ticker::ARKK, Some(dir.path().to_owned()), dir.transport())?
+            .backfill(day(4, 1), day(10, 16), Source::ApiFull)
+            .await?;
+
+        let written = Ark::read_parquet(Ticker::ARKK, Some(&dir.path().to_owned()))?.collect()?;
+        let table =
+            security_master::read_securities(&format!("{}/securities.parquet", dir.path()))?;
+        assert_eq!(written.height(), expected);
+        assert_eq!(
+            table["88160R101"].funds.iter().collect::<Vec<_>>(),
+            ["ARKK"]
+        );
+        assert!(!Path::new(&checkpoint).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn arkfunds_io_full_window() -> Result<(), Error> {
+        let dir = TestDir::new("arkfunds_io_window")?;
+        dir.fixture(
+            &format!(
+                "{}/etf/holdings?symbol=ARKK&date_from=2026-07-01&date_to=2026-09-30",
+                UPSTREAM.arkfunds_io
+            ),
+            "[]",
+        )?;
+
+        // a bounded window asks for its own start, not the whole history
+        let df = Ark::open(Ticker::ARKK, Some(dir.path().to_owned()), dir.transport())?
+            .get_api(
+                NaiveDate::from_ymd_opt(2026, 7, 1),
+                NaiveDate::from_ymd_opt(2026, 9, 30),
+                Some(&Source::ArkFundsIoFull),
+            )
+            .await?;
+
+        assert_eq!(df.height(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fill_gaps() -> Result<(), Error> {
+        let dir = TestDir::new("gaps")?;
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        let existing = Reader::Json.parse(tesla_json(&["2026-10-13", "2026-10-16"]).into())?;
+        Ark::write_df_parquet(
+            &format!("{}/ARKK.parquet", dir.path()),
+            Ark::df_format(existing.into(), None, None)?,
+        )?;
+        dir.fixture(
+            &format!(
+                "{}/ark_holdings?ticker=ARKK&start=2026-10-14&end=2026-10-15",
+                UPSTREAM.api
+            ),
+            tesla_json(&["2026-10-14"]),
+        )?;
+
+        let mut ark = Ark::open(Ticker::ARKK, Some(dir.path().to_owned()), dir.transport())?;
+        let gaps = ark.gaps()?;
+        ark.fill_gaps(Source::ApiIncremental).await?;
+
+        assert_eq!(gaps, vec![(day(14), day(15))]);
+        assert_eq!(ark.gaps()?, vec![(day(15), day(15))]);
+        assert_eq!(ark.collect()?.height(), 3);
+        Ok(())
+    }
 }
@@ -0,0 +1,88 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::PathBuf,
+};
+
+use anyhow::{Error, Result};
+use chrono::NaiveDate;
+use glob::glob;
+use sha2::{Digest, Sha256};
+
+use crate::util::ticker::Ticker;
+
+/// Byte for byte copies of every downloaded payload, stored as
+/// `{dir}/{ticker}/{as_of_date}-{sha256}.{extension}`
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    // Returns the existing file if the same payload was already archived
+    pub fn save(
+        &self,
+        ticker: Ticker,
+        as_of: NaiveDate,
+        extension: &str,
+        body: &[u8],
+    ) -> Result<PathBuf, Error> {
+        let hash = format!("{:x}", Sha256::digest(body));
+        let dir = self.dir.join(ticker.to_string());
+
+        let pattern = dir.join(format!("*-{hash}.{extension}"));
+        if let Some(existing) = glob(&pattern.to_string_lossy())?.find_map(Result::ok) {
+            return Ok(existing);
+        }
+
+        create_dir_all(&dir)?;
+        let path = dir.join(format!("{as_of}-{hash}.{extension}"));
+        fs::write(&path, body)?;
+        Ok(path)
+    }
+
+    // Oldest first, file names start with the as of date
+    pub fn files(&self, ticker: Ticker) -> Result<Vec<PathBuf>, Error> {
+        let pattern = self.dir.join(ticker.to_string()).join("*");
+        let mut files: Vec<_> = glob(&pattern.to_string_lossy())?
+            .filter_map(Result::ok)
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn dedupes_by_hash() -> Result<(), Error> {
+        let dir = TestDir::new("archive_dedupe")?;
+        let archive = Archive::new(dir.path());
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+
+        let first = archive.save(Ticker::ARKK, day(15), "csv", b"a,b\n1,2\n")?;
+        let again = archive.save(Ticker::ARKK, day(16), "csv", b"a,b\n1,2\n")?;
+        let other = archive.save(Ticker::ARKK, day(16), "csv", b"a,b\n3,4\n")?;
+        let count = archive.files(Ticker::ARKK)?.len();
+
+        assert_eq!(first, again);
+        assert_eq!(
+            first,
+            PathBuf::from(format!(
+                "{}/ARKK/2026-10-15-{:x}.csv",
+                dir.path(),
+                Sha256::digest(b"a,b\n1,2\n")
+            ))
+        );
+        assert_eq!(count, 2);
+        assert!(other.to_string_lossy().contains("ARKK/2026-10-16-"));
+        Ok(())
+    }
+}
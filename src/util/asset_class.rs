@@ -0,0 +1,132 @@
+use polars::prelude::*;
+use strum::IntoEnumIterator;
+use strum_macros::Display;
+
+use crate::util::{identifier, ticker::Ticker};
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum AssetClass {
+    Equity,
+    // cash, money market funds and treasuries
+    Cash,
+    Crypto,
+    // ETFs, including ARK's own
+    Fund,
+    // no ticker and no valid identifier, mostly ARKVX
+    Private,
+    Derivative,
+}
+
+// Names are only matched for holdings without a valid identifier, so an
+// equity like "CASH AMERICA" or "FORWARD INDUSTRIES" stays an equity
+
+// "Cash & Other", "US DOLLAR", "GOLDMAN FS TRSY OBLIG INST 468", "TREASURY BILL 11/21/24"
+const CASH: &str = r"(?i)^(CASH( ?&| AND |_USD|$)|US DOLLARS?$|USD$)|\bMONEY MARKET\b|\b(TRSY|TREASURY|GOVT) OBLIG\b|^(US |UNITED STATES )?(TREASURY|TRSY) (BILL|NOTE|BOND)S?\b";
+// Bills, notes and bonds of the US Treasury
+const TREASURY_CUSIP: &str = r"^(US)?912(79[67]|810|828|82C)";
+// a contract with its expiry or strike, "CME BITCOIN FUT Jun26", "TSLA CALL 250 01/17/25"
+const DERIVATIVE: &str =
+    r"(?i)\bFUT(URES?)?\s+[A-Z]{3}\d{2}$|\b(CALLS?|PUTS?|OPTIONS?|SWAPS?|FORWARDS?|FWD)\b.*\d";
+// root, month code and year digit, "BTCV6"
+const FUTURE_TICKER: &str = r"^[A-Z]{1,4}[FGHJKMNQUVXZ]\d$";
+const CRYPTO: &str = r"(?i)^(BITCOIN|ETHEREUM|ETHER|SOLANA)$";
+const CRYPTO_TICKERS: [&str; 4] = ["BTC", "XBT", "ETH", "SOL"];
+const FUND: &str = r"(?i)\b(ETF|FUND|HOLDCO)\b";
+// MoneyMarketFlag of the 21Shares csv
+const MONEY_MARKET: &str = r"(?i)^(Y|YES|TRUE|1)$";
+
+fn any_ticker<'a>(tickers: impl IntoIterator<Item = &'a str>) -> Expr {
+    tickers
+        .into_iter()
+        .fold(lit(false), |expr, x| expr.or(col("ticker").eq(lit(x))))
+}
+
+impl AssetClass {
+    /// Polars expression with the asset class of every row, the first class
+    /// that matches wins. `money_market` is the flag column, if the file has
+    /// one.
+    pub fn expr(money_market: Option<&str>) -> Expr {
+        let company = |pattern: &str| col("company").str().contains(lit(pattern), false);
+
+        let no_id =
+            identifier::id_type_expr(col("cusip")).eq(lit(identifier::IdType::Unknown.to_string()));
+
+        let mut cash = col("ticker")
+            .eq(lit("CASH_USD"))
+            .or(col("cusip").str().contains(lit(TREASURY_CUSIP), false))
+            .or(no_id.clone().and(company(CASH)));
+        if let Some(flag) = money_market {
+            cash = cash.or(col(flag)
+                .cast(DataType::Utf8)
+                .str()
+                .contains(lit(MONEY_MARKET), false));
+        }
+        let derivative = no_id
+            .clone()
+            .and(company(DERIVATIVE).or(col("ticker").str().contains(lit(FUTURE_TICKER), false)));
+        let fund_tickers: Vec<String> = Ticker::iter().map(|x| x.to_string()).collect();
+        let private = col("ticker").is_null().and(no_id);
+
+        when(cash)
+            .then(lit(Self::Cash.to_string()))
+            .when(derivative)
+            .then(lit(Self::Derivative.to_string()))
+            .when(company(CRYPTO).or(any_ticker(CRYPTO_TICKERS)))
+            .then(lit(Self::Crypto.to_string()))
+            .when(company(FUND).or(any_ticker(fund_tickers.iter().map(String::as_str))))
+            .then(lit(Self::Fund.to_string()))
+            .when(private)
+            .then(lit(Self::Private.to_string()))
+            .otherwise(lit(Self::Equity.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Error, Result};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::equity(Some("TSLA"), Some("88160R101"), "TESLA", "N", "equity")]
+    #[case::cash_usd(Some("CASH_USD"), None, "CASH_USD", "N", "cash")]
+    #[case::treasury_fund(None, Some("X9USDGSTR"), "GOLDMAN FS TRSY OBLIG INST 468", "N", "cash")]
+    #[case::cash_label(None, None, "Cash & Other", "N", "cash")]
+    #[case::treasury_bill(None, Some("912797KX4"), "TREASURY BILL 11/21/24", "N", "cash")]
+    #[case::money_market_flag(Some("MMF"), Some("X"), "DREYFUS GOVT", "Y", "cash")]
+    #[case::crypto(Some("BTC"), Some("-"), "Bitcoin", "N", "crypto")]
+    #[case::fund(Some("ARKB"), None, "ARKB", "N", "fund")]
+    #[case::private(None, Some("SPACEX"), "SpaceX", "N", "private")]
+    #[case::future(Some("BTCV6"), None, "CME BITCOIN FUT Jun26", "N", "derivative")]
+    #[case::future_ticker(Some("ESZ5"), None, "S&P500 EMINI", "N", "derivative")]
+    #[case::option(None, None, "TSLA CALL 250 01/17/25", "N", "derivative")]
+    // equities with cash or derivative words in their name
+    #[case::cash_america(Some("CSH"), Some("14754D100"), "CASH AMERICA", "N", "equity")]
+    #[case::swap(Some("SWAP"), Some("87000F103"), "SWAP INC", "N", "equity")]
+    #[case::forward(Some("FORD"), Some("349863100"), "FORWARD INDUSTRIES", "N", "equity")]
+    #[case::treasury_wine(None, Some("AU000000TWE9"), "TREASURY WINE ESTATES", "N", "equity")]
+    #[case::options_no_id(Some("OPTN"), None, "OPTIONS GROUP", "N", "equity")]
+    fn classes(
+        #[case] ticker: Option<&str>,
+        #[case] cusip: Option<&str>,
+        #[case] company: &str,
+        #[case] money_market: &str,
+        #[case] expected: &str,
+    ) -> Result<(), Error> {
+        let df = df![
+            "ticker" => [ticker],
+            "cusip" => [cusip],
+            "company" => [company],
+            "money_market" => [money_market],
+        ]?
+        .lazy()
+        .select([AssetClass::expr(Some("money_market")).alias("asset_class")])
+        .collect()?;
+
+        assert_eq!(df.column("asset_class")?.utf8()?.get(0), Some(expected));
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::PathBuf,
+};
+
+use anyhow::{Error, Result};
+use serde_json::{Value, json};
+
+use crate::util::transport::{Request, Response, fixture_name};
+
+/// Remembers the `ETag` and `Last-Modified` validators of each url, so the
+/// next download can be a conditional request
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn entry(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", fixture_name(url)))
+    }
+
+    pub fn request(&self, url: &str) -> Result<Request, Error> {
+        let mut request = Request::new(url);
+        let entry = self.entry(url);
+        if !entry.exists() {
+            return Ok(request);
+        }
+
+        let entry: Value = serde_json::from_slice(&fs::read(entry)?)?;
+        if let Some(etag) = entry["etag"].as_str() {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = entry["last_modified"].as_str() {
+            request = request.header("If-Modified-Since", last_modified);
+        }
+        Ok(request)
+    }
+
+    pub fn store(&self, url: &str, response: &Response) -> Result<(), Error> {
+        let etag = response.header("etag");
+        let last_modified = response.header("last-modified");
+        if etag.is_none() && last_modified.is_none() {
+            return Ok(());
+        }
+
+        create_dir_all(&self.dir)?;
+        let entry = json!({
+            "url": url,
+            "etag": etag,
+            "last_modified": last_modified,
+        });
+        fs::write(self.entry(url), serde_json::to_vec_pretty(&entry)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn conditional_request() -> Result<(), Error> {
+        let dir = TestDir::new("cache_conditional")?;
+        let url = "https://example.com/holdings.csv";
+        let cache = HttpCache::new(dir.path());
+
+        let before = cache.request(url)?;
+        cache.store(
+            url,
+            &Response {
+                status: 200,
+                headers: vec![
+                    ("ETag".to_owned(), "\"abc\"".to_owned()),
+                    (
+                        "Last-Modified".to_owned(),
+                        "Wed, 21 Oct 2015 07:28:00 GMT".to_owned(),
+                    ),
+                ],
+                body: vec![],
+            },
+        )?;
+        let after = cache.request(url)?;
+
+        assert_eq!(before, Request::new(url));
+        assert_eq!(
+            after,
+            Request::new(url)
+                .header("If-None-Match", "\"abc\"")
+                .header("If-Modified-Since", "Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        Ok(())
+    }
+}
@@ -1,41 +1,238 @@
-use std::io::Cursor;
+use std::{fmt, io::Cursor};
 
 use anyhow::{Error, anyhow};
+use calamine::{Data, DataType as _, Reader as _, Xlsx, open_workbook_from_rs};
+use chrono::NaiveDate;
 use polars::{
     frame::DataFrame,
     io::SerReader,
-    prelude::{CsvReader, JsonReader},
+    prelude::{CsvReader, JsonReader, NamedFrom},
+    series::Series,
 };
-use reqwest::blocking::Client;
 use serde_json::Value;
 
+use crate::util::{
+    ticker::DataSource,
+    transport::{Request, Response, Transport},
+};
+
+// Anything shorter can't hold a header row and one holding
+const MIN_CSV_BYTES: usize = 32;
+// `[]` is a valid answer for a day without holdings
+const MIN_JSON_BYTES: usize = 2;
+// An empty zip archive alone is 22 bytes
+const MIN_XLSX_BYTES: usize = 100;
+// Europe downloads have a few title rows above the header
+const HEADER_SEARCH_LINES: usize = 10;
+// Title rows have a single cell, the holdings table starts at the first row
+// with at least this many
+const MIN_TABLE_COLUMNS: usize = 3;
+
+/// Returned when a conditional request is answered with `304 Not Modified`
+#[derive(Debug)]
+pub struct NotModified(pub String);
+
+impl fmt::Display for NotModified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} has not changed since the last download", self.0)
+    }
+}
+
+impl std::error::Error for NotModified {}
+
+/// Returned by [`Reader::check`] when a download is clearly not the data that
+/// was asked for, such as an HTML challenge page served with status 200
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BadResponse {
+    Html {
+        url: String,
+        expected: Reader,
+    },
+    ContentType {
+        url: String,
+        content_type: String,
+        expected: Reader,
+    },
+    Empty {
+        url: String,
+    },
+    Unreadable {
+        url: String,
+        expected: Reader,
+    },
+    TooSmall {
+        url: String,
+        size: usize,
+        min: usize,
+    },
+    MissingHeader {
+        url: String,
+        data_source: DataSource,
+        missing: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for BadResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Html { url, expected } => write!(f, "{url}: got HTML, expected {expected}"),
+            Self::ContentType {
+                url,
+                content_type,
+                expected,
+            } => write!(f, "{url}: got {content_type}, expected {expected}"),
+            Self::Empty { url } => write!(f, "{url}: got an empty body"),
+            Self::Unreadable { url, expected } => write!(f, "{url}: body is not {expected}"),
+            Self::TooSmall { url, size, min } => {
+                write!(f, "{url}: got {size} bytes, expected at least {min}")
+            }
+            Self::MissingHeader {
+                url,
+                data_source,
+                missing,
+            } => write!(
+                f,
+                "{url}: header row is missing {} for {data_source:?}",
+                missing.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BadResponse {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Reader {
     Csv,
     Json,
+    Xlsx,
+}
+
+impl fmt::Display for Reader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "CSV"),
+            Self::Json => write!(f, "JSON"),
+            Self::Xlsx => write!(f, "XLSX"),
+        }
+    }
 }
 
 impl Reader {
-    pub fn get_data_url(&self, url: String) -> anyhow::Result<DataFrame, Error> {
-        let response = Client::builder()
-            .user_agent(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
-                 Chrome/119.0.0.0 Safari/537.36",
-            )
-            .gzip(true)
-            .build()?
-            .get(url)
-            .send()?;
-
-        if !response.status().is_success() {
+    pub async fn get_data_url(
+        &self,
+        transport: &dyn Transport,
+        url: String,
+    ) -> anyhow::Result<DataFrame, Error> {
+        let response = Self::fetch(transport, &Request::new(url.clone())).await?;
+        self.check(&url, &response, None)?;
+        self.parse(response.body)
+    }
+
+    pub async fn fetch(
+        transport: &dyn Transport,
+        request: &Request,
+    ) -> anyhow::Result<Response, Error> {
+        let response = transport.get(request).await?;
+
+        if response.status == 304 {
+            return Err(NotModified(request.url.clone()).into());
+        }
+        if !response.is_success() {
             return Err(anyhow!(
                 "HTTP request failed with status code: {:?}",
-                response.status()
+                response.status
             ));
         }
 
-        let data = response.text()?.into_bytes();
+        Ok(response)
+    }
+
+    /// Cheap checks on a successful response before it is handed to polars:
+    /// content type, leading bytes, size and, for csv, the header row of
+    /// `data_source`
+    pub fn check(
+        &self,
+        url: &str,
+        response: &Response,
+        data_source: Option<DataSource>,
+    ) -> Result<(), BadResponse> {
+        let url = url.to_owned();
+        let expected = *self;
+
+        if let Some(content_type) = response.header("content-type") {
+            let content_type = content_type.to_ascii_lowercase();
+            if content_type.contains("html") {
+                return Err(BadResponse::Html { url, expected });
+            }
+            let wrong = match self {
+                Self::Csv => content_type.contains("json"),
+                Self::Json => content_type.contains("csv"),
+                Self::Xlsx => content_type.contains("json") || content_type.contains("csv"),
+            };
+            if wrong {
+                return Err(BadResponse::ContentType {
+                    url,
+                    content_type,
+                    expected,
+                });
+            }
+        }
+
+        let body = String::from_utf8_lossy(&response.body);
+        let body = body.trim_start_matches('\u{feff}').trim_start();
+        if body.is_empty() {
+            return Err(BadResponse::Empty { url });
+        }
+        if body.starts_with('<') {
+            return Err(BadResponse::Html { url, expected });
+        }
+        if *self == Self::Xlsx && !Self::is_xlsx(response) {
+            return Err(BadResponse::Unreadable { url, expected });
+        }
+
+        let min = match self {
+            Self::Csv => MIN_CSV_BYTES,
+            Self::Json => MIN_JSON_BYTES,
+            Self::Xlsx => MIN_XLSX_BYTES,
+        };
+        if body.len() < min {
+            return Err(BadResponse::TooSmall {
+                url,
+                size: body.len(),
+                min,
+            });
+        }
+
+        if let (Self::Csv, Some(data_source)) = (self, data_source) {
+            // columns missing from the line that looks most like a header
+            let missing = body
+                .lines()
+                .take(HEADER_SEARCH_LINES)
+                .map(|line| {
+                    let line = line.to_ascii_lowercase();
+                    data_source
+                        .header_columns()
+                        .iter()
+                        .copied()
+                        .filter(|col| !line.contains(&col.to_ascii_lowercase()))
+                        .collect::<Vec<_>>()
+                })
+                .min_by_key(Vec::len)
+                .unwrap_or_default();
+            if !missing.is_empty() {
+                return Err(BadResponse::MissingHeader {
+                    url,
+                    data_source,
+                    missing,
+                });
+            }
+        }
 
+        Ok(())
+    }
+
+    pub fn parse(&self, data: Vec<u8>) -> anyhow::Result<DataFrame, Error> {
         let df = match self {
             Self::Csv => CsvReader::new(Cursor::new(data))
                 .has_header(true)
@@ -43,9 +240,257 @@ impl Reader {
             Self::Json => {
                 let json_string = String::from_utf8(data)?;
                 let json: Value = serde_json::from_str(&json_string)?;
+                if json.as_array().is_some_and(Vec::is_empty) {
+                    return Ok(DataFrame::empty());
+                }
                 JsonReader::new(Cursor::new(json.to_string())).finish()?
             }
+            Self::Xlsx => read_xlsx(data)?,
         };
         Ok(df)
     }
+
+    /// Xlsx files are zip archives
+    #[must_use]
+    pub fn is_xlsx(response: &Response) -> bool {
+        response.body.starts_with(b"PK\x03\x04")
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+// Reads the holdings table of the first sheet, the as-of date from the title
+// rows above it becomes the `date` column
+fn read_xlsx(data: Vec<u8>) -> Result<DataFrame, Error> {
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow!("Workbook has no sheets"))??;
+    let rows: Vec<&[Data]> = range.rows().collect();
+
+    let header = rows
+        .iter()
+        .position(|row| row.iter().filter(|x| !x.is_empty()).count() >= MIN_TABLE_COLUMNS)
+        .ok_or_else(|| anyhow!("Workbook has no holdings table"))?;
+    let as_of = rows[..header].iter().copied().flatten().find_map(cell_date);
+
+    // the table ends at the first empty row, notes follow below it
+    let body: Vec<_> = rows[header + 1..]
+        .iter()
+        .take_while(|row| row.iter().any(|x| !x.is_empty()))
+        .collect();
+
+    let mut columns: Vec<Series> = rows[header]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| Some((i, cell_string(name)?)))
+        .map(|(i, name)| {
+            let values: Vec<_> = body.iter().map(|row| cell_string(&row[i])).collect();
+            Series::new(&name, values)
+        })
+        .collect();
+    if let Some(as_of) = as_of {
+        columns.push(Series::new("date", vec![as_of; body.len()]));
+    }
+
+    Ok(DataFrame::new(columns)?)
+}
+
+fn cell_string(cell: &Data) -> Option<String> {
+    match cell {
+        Data::Empty | Data::Error(_) => None,
+        Data::String(x) => Some(x.trim().to_owned()).filter(|x| !x.is_empty()),
+        x => Some(x.to_string()),
+    }
+}
+
+fn cell_date(cell: &Data) -> Option<NaiveDate> {
+    match cell {
+        Data::String(x) => as_of_date(x),
+        x => x.as_date(),
+    }
+}
+
+/// Finds a date in a title row such as `Holdings as of 16/10/2026`, European
+/// day first formats are tried before iso dates
+#[must_use]
+pub fn as_of_date(text: &str) -> Option<NaiveDate> {
+    let words: Vec<_> = text
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ':' | ';' | '(' | ')'))
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    let numeric = words.iter().find_map(|word| {
+        ["%d/%m/%Y", "%d.%m.%Y", "%d-%m-%Y", "%Y-%m-%d"]
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(word, format).ok())
+    });
+    numeric.or_else(|| {
+        words.windows(3).find_map(|x| {
+            let text = x.join(" ");
+            ["%d %B %Y", "%B %d %Y", "%d %b %Y", "%b %d %Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(&text, format).ok())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::df;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::test_utils::xlsx;
+
+    const URL: &str = "https://example.com/holdings";
+    const ARK_CSV: &str =
+        "date,fund,company,ticker,cusip,shares,\"market value ($)\",\"weight (%)\"\n";
+
+    fn response(content_type: Option<&str>, body: &str) -> Response {
+        Response {
+            status: 200,
+            headers: content_type
+                .map(|x| vec![("Content-Type".to_owned(), x.to_owned())])
+                .unwrap_or_default(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[rstest]
+    #[case::ark_csv(Reader::Csv, Some("text/csv"), ARK_CSV, Some(DataSource::Ark), None)]
+    #[case::html_content_type(
+        Reader::Csv,
+        Some("text/html; charset=utf-8"),
+        ARK_CSV,
+        None,
+        Some(BadResponse::Html { url: URL.to_owned(), expected: Reader::Csv })
+    )]
+    #[case::html_body(
+        Reader::Csv,
+        None,
+        "\n<!DOCTYPE html><html><title>Just a moment...</title></html>",
+        None,
+        Some(BadResponse::Html { url: URL.to_owned(), expected: Reader::Csv })
+    )]
+    #[case::json_as_csv(
+        Reader::Json,
+        Some("text/csv"),
+        "[]",
+        None,
+        Some(BadResponse::ContentType {
+            url: URL.to_owned(),
+            content_type: "text/csv".to_owned(),
+            expected: Reader::Json
+        })
+    )]
+    #[case::empty(Reader::Csv, None, " \n", None, Some(BadResponse::Empty { url: URL.to_owned() }))]
+    #[case::too_small(
+        Reader::Csv,
+        None,
+        "date,ticker\n",
+        None,
+        Some(BadResponse::TooSmall { url: URL.to_owned(), size: 12, min: MIN_CSV_BYTES })
+    )]
+    #[case::empty_json_array(Reader::Json, Some("application/json"), "[]", None, None)]
+    #[case::wrong_header(
+        Reader::Csv,
+        None,
+        ARK_CSV,
+        Some(DataSource::Shares21),
+        Some(BadResponse::MissingHeader {
+            url: URL.to_owned(),
+            data_source: DataSource::Shares21,
+            missing: vec!["StockTicker", "SecurityName"]
+        })
+    )]
+    #[case::xlsx_not_zip(
+        Reader::Xlsx,
+        Some("application/octet-stream"),
+        "name,ISIN,Weight\nNVIDIA,US67066G1040,5.1\n",
+        None,
+        Some(BadResponse::Unreadable { url: URL.to_owned(), expected: Reader::Xlsx })
+    )]
+    #[case::europe_title_rows(
+        Reader::Csv,
+        None,
+        "ARK Artificial Intelligence,,\nAs of 16/10/2026,,\nname,ISIN,Weight\n",
+        Some(DataSource::ArkEurope),
+        None
+    )]
+    fn check(
+        #[case] reader: Reader,
+        #[case] content_type: Option<&str>,
+        #[case] body: &str,
+        #[case] data_source: Option<DataSource>,
+        #[case] expected: Option<BadResponse>,
+    ) {
+        assert_eq!(
+            reader
+                .check(URL, &response(content_type, body), data_source)
+                .err(),
+            expected
+        );
+    }
+
+    #[test]
+    fn html_error_message() {
+        let err = Reader::Csv
+            .check(URL, &response(Some("text/html"), ARK_CSV), None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "https://example.com/holdings: got HTML, expected CSV"
+        );
+    }
+
+    #[test]
+    fn read_xlsx_table() -> Result<(), Error> {
+        let data = xlsx(&[
+            &["ARK Artificial Intelligence & Robotics UCITS ETF"],
+            &["Holdings as of 16/10/2026"],
+            &[],
+            &["name", "ISIN", "Currency", "Weight"],
+            &["NVIDIA CORP", "US67066G1040", "USD", "5.12"],
+            &["TESLA INC", "US88160R1014", "USD", "4.80"],
+            &[],
+            &["Holdings are subject to change"],
+        ])?;
+        let df = Reader::Xlsx.parse(data)?;
+
+        let as_of = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        assert_eq!(
+            df,
+            df![
+                "name" => ["NVIDIA CORP", "TESLA INC"],
+                "ISIN" => ["US67066G1040", "US88160R1014"],
+                "Currency" => ["USD", "USD"],
+                "Weight" => ["5.12", "4.80"],
+                "date" => [as_of, as_of],
+            ]?
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::slash("Holdings as of 16/10/2026", NaiveDate::from_ymd_opt(2026, 10, 16))]
+    #[case::dot("Stand: 16.10.2026", NaiveDate::from_ymd_opt(2026, 10, 16))]
+    #[case::iso("As of 2026-10-16", NaiveDate::from_ymd_opt(2026, 10, 16))]
+    #[case::long(
+        "Holdings as of 16 October 2026",
+        NaiveDate::from_ymd_opt(2026, 10, 16)
+    )]
+    #[case::us_long("As of October 16, 2026", NaiveDate::from_ymd_opt(2026, 10, 16))]
+    #[case::none("ARK Artificial Intelligence & Robotics UCITS ETF", None)]
+    fn as_of(#[case] text: &str, #[case] expected: Option<NaiveDate>) {
+        assert_eq!(as_of_date(text), expected);
+    }
 }
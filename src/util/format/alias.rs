@@ -0,0 +1,361 @@
+use std::{env, fs, sync::LazyLock};
+
+use anyhow::{Error, Result, anyhow};
+use chrono::NaiveDate;
+use polars::prelude::*;
+use serde_json::Value;
+
+use crate::util::df::DF;
+
+const ALIASES_VERSION: u64 = 1;
+const DEFAULT_ALIASES: &str = include_str!("../../../rules/aliases.json");
+
+/// Ticker aliases, read once at startup from the file in `ARK_ALIASES` or the
+/// `rules/aliases.json` built into the binary
+pub static ALIASES: LazyLock<Aliases> =
+    LazyLock::new(|| Aliases::from_env().expect("Ticker aliases are invalid"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Company,
+    Ticker,
+    Cusip,
+}
+
+impl Key {
+    const fn column(self) -> &'static str {
+        match self {
+            Self::Company => "company",
+            Self::Ticker => "ticker",
+            Self::Cusip => "cusip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub key: Key,
+    pub value: String,
+    // canonical values, None leaves the column as is
+    pub ticker: Option<String>,
+    pub company: Option<String>,
+    // inclusive, only used for point in time formatting
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aliases {
+    pub version: u64,
+    // applied in file order, every alias sees the output of the ones before
+    pub aliases: Vec<Alias>,
+}
+
+impl Aliases {
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var("ARK_ALIASES") {
+            Ok(path) => {
+                Self::parse(&fs::read_to_string(&path)?).map_err(|e| anyhow!("{path}: {e}"))
+            }
+            Err(_) => Self::parse(DEFAULT_ALIASES),
+        }
+    }
+
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        let json: Value = serde_json::from_str(json)?;
+
+        let version = json["version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Aliases have no version"))?;
+        if version != ALIASES_VERSION {
+            return Err(anyhow!(
+                "Aliases version {version} is not supported, expected {ALIASES_VERSION}"
+            ));
+        }
+
+        let aliases = json["aliases"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Aliases have no aliases array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Alias::parse(x).map_err(|e| anyhow!("Alias {i}: {e}")))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self { version, aliases })
+    }
+
+    /// Rewrites every row to its canonical ticker and company. With
+    /// `point_in_time` an alias only applies to rows inside its date range,
+    /// so history keeps the symbol that was valid at the time.
+    pub fn apply(&self, df: DF, point_in_time: bool) -> Result<DF, Error> {
+        let mut df = df.collect()?;
+        let cols: Vec<String> = df
+            .get_column_names()
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect();
+        let has = |name: &str| cols.iter().any(|x| x == name);
+        if !has("ticker") || !has("company") {
+            return Ok(df.into());
+        }
+
+        for alias in &self.aliases {
+            let dated = point_in_time && (alias.from.is_some() || alias.to.is_some());
+            if !has(alias.key.column()) || (dated && !has("date")) {
+                continue;
+            }
+            df = df
+                .lazy()
+                .with_columns(alias.exprs(point_in_time))
+                .collect()?;
+        }
+        Ok(df.into())
+    }
+}
+
+impl Alias {
+    fn parse(json: &Value) -> Result<Self, Error> {
+        let Some((key, value)) = json["match"]
+            .as_object()
+            .filter(|x| x.len() == 1)
+            .and_then(|x| x.iter().next())
+        else {
+            return Err(anyhow!("{} is not a match", json["match"]));
+        };
+        let key = match key.as_str() {
+            "company" => Key::Company,
+            "ticker" => Key::Ticker,
+            "cusip" => Key::Cusip,
+            x => return Err(anyhow!("{x} is not a match key")),
+        };
+        let value = value
+            .as_str()
+            .ok_or_else(|| anyhow!("{value} is not a string"))?
+            .to_owned();
+
+        let text = |name: &str| json[name].as_str().map(ToOwned::to_owned);
+        let date = |name: &str| {
+            text(name)
+                .map(|x| NaiveDate::parse_from_str(&x, "%Y-%m-%d"))
+                .transpose()
+                .map_err(|e| anyhow!("{name}: {e}"))
+        };
+
+        let alias = Self {
+            key,
+            value,
+            ticker: text("ticker"),
+            company: text("company"),
+            from: date("from")?,
+            to: date("to")?,
+        };
+
+        if alias.ticker.is_none() && alias.company.is_none() {
+            return Err(anyhow!("{} has no ticker or company", alias.value));
+        }
+        if let (Some(from), Some(to)) = (alias.from, alias.to)
+            && from > to
+        {
+            return Err(anyhow!("{} ends before it starts", alias.value));
+        }
+        Ok(alias)
+    }
+
+    fn exprs(&self, point_in_time: bool) -> Vec<Expr> {
+        let mut matches = col(self.key.column()).eq(lit(self.value.as_str()));
+        if point_in_time {
+            let date = || col("date").cast(DataType::Date);
+            if let Some(from) = self.from {
+                matches = matches.and(date().gt_eq(lit(from)));
+            }
+            if let Some(to) = self.to {
+                matches = matches.and(date().lt_eq(lit(to)));
+            }
+        }
+
+        // both read the values from before the alias
+        [("ticker", &self.ticker), ("company", &self.company)]
+            .into_iter()
+            .filter_map(|(name, canonical)| {
+                Some(
+                    when(matches.clone())
+                        .then(lit(canonical.as_deref()?))
+                        .otherwise(col(name))
+                        .alias(name),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[rstest]
+    #[case::arkb(
+        defualt_df(
+            &[None::<&str>, Some("ARKB"), Some("ARKB"), Some("ARKB")],
+            &[
+                Some("ARK BITCOIN ETF HOLDCO (ARKW)"),
+                Some("ARK BITCOIN ETF HOLDCO (ARKW)"),
+                Some("ARK BITCOIN ETF HOLDCO (ARKF)"),
+                Some("ARKB"),
+            ],
+        )?,
+        defualt_df(
+            &[Some("ARKB"), Some("ARKB"), Some("ARKB"), Some("ARKB")],
+            &[Some("ARKB"), Some("ARKB"), Some("ARKB"), Some("ARKB")],
+        )?,
+    )]
+    #[case::crlc(
+        defualt_df(
+            &[Some("CRLC"), None::<&str>],
+            &[Some("CIRCLE INTERNET GROUP"), Some("CIRCLE INTERNET GROUP")],
+        )?,
+        defualt_df(
+            &[Some("CRLC"), Some("CRLC")],
+            &[Some("CIRCLE INTERNET GROUP"), Some("CIRCLE INTERNET GROUP")]
+        )?,
+    )]
+    #[case::crwv(
+        defualt_df(
+            &[Some("CRWV"), None::<&str>],
+            &[Some("COREWEAVE"), Some("COREWEAVE")],
+        )?,
+        defualt_df(
+            &[Some("CRWV"), Some("CRWV")],
+            &[Some("COREWEAVE"), Some("COREWEAVE")]
+        )?,
+    )]
+    #[case::etor(
+        defualt_df(
+            &[Some("ETOR"), None::<&str>],
+            &[Some("ETORO GROUP"), Some("ETORO GROUP")],
+        )?,
+        defualt_df(
+            &[Some("ETOR"), Some("ETOR")],
+            &[Some("ETORO GROUP"), Some("ETORO GROUP")]
+        )?,
+    )]
+    #[case::mkfg(
+        defualt_df(
+            &[Some("MKFG"), None::<&str>],
+            &[Some("MARKFORGEDG"), Some("MARKFORGEDG")],
+        )?,
+        defualt_df(
+            &[Some("MKFG"), Some("MKFG")],
+            &[Some("MARKFORGEDG"), Some("MARKFORGEDG")]
+        )?,
+    )]
+    #[case::lunr(
+        defualt_df(
+            &[Some("LUNR"), None::<&str>],
+            &[Some("INTUITIVE MACHINES"), Some("INTUITIVE MACHINES")]
+        )?,
+        defualt_df(
+            &[Some("LUNR"), Some("LUNR")],
+            &[Some("INTUITIVE MACHINES"), Some("INTUITIVE MACHINES")]
+        )?,
+    )]
+    #[case::xyz(
+        defualt_df(
+            &[Some("SQ"), Some("SQ"), Some("XYZ"), Some("XYZ")],
+            &[Some("Block"), Some("BLOCK"), Some("Block"), Some("BLOCK")],
+        )?,
+        defualt_df(
+            &[Some("XYZ"), Some("XYZ"), Some("XYZ"), Some("XYZ")],
+            &[Some("Block"), Some("BLOCK"), Some("Block"), Some("BLOCK")],
+        )?,
+    )]
+    #[case::cash_usd(
+        defualt_df(
+            &[None::<&str>, None::<&str>, None::<&str>, Some("CASH&Other")],
+            &[Some("Cash & Cash Equivalents"), Some("CASH & CASH EQUIVALENTS"), Some("GOLDMAN FS TRSY OBLIG INST 468"), Some("Cash & Other")],
+        )?,
+        defualt_df(
+            &[Some("CASH_USD"), Some("CASH_USD"), Some("CASH_USD"), Some("CASH_USD")],
+            &[Some("CASH_USD"), Some("CASH_USD"), Some("CASH_USD"), Some("CASH_USD")],
+        )?,
+    )]
+    #[case::rklb(
+        defualt_df(
+            &[Some("RKLB"), Some("RKLB"), None::<&str>],
+            &[Some("ROCKET LAB"), Some("ROCKET LAB USA"), Some("ROCKET LAB")],
+        )?,
+        defualt_df(
+            &[Some("RKLB"), Some("RKLB"), Some("RKLB")],
+            &[Some("ROCKET LAB"), Some("ROCKET LAB"), Some("ROCKET LAB")]
+        )?,
+    )]
+    // names stored by the company rules before version 2
+    #[case::tsm(
+        defualt_df(
+            &[Some("TSM"), Some("TSM")],
+            &[Some("TMSC"), Some("TAIWAN SEMICONDUCTOR")],
+        )?,
+        defualt_df(
+            &[Some("TSM"), Some("TSM")],
+            &[Some("TAIWAN SEMICONDUCTOR"), Some("TAIWAN SEMICONDUCTOR")]
+        )?,
+    )]
+    fn matrix(#[case] input: DataFrame, #[case] expected: DataFrame) -> Result<(), Error> {
+        let aliases = Aliases::parse(DEFAULT_ALIASES)?;
+        let formatted_df = aliases.apply(input.into(), false)?.collect()?;
+        assert_eq!(formatted_df, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn point_in_time() -> Result<(), Error> {
+        let aliases = Aliases::parse(DEFAULT_ALIASES)?;
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let df = df![
+            "date" => [day(2024, 12, 31), day(2025, 1, 21)],
+            "ticker" => ["SQ", "SQ"],
+            "company" => ["BLOCK", "BLOCK"],
+        ]?;
+
+        let ticker = |point_in_time| -> Result<Vec<Option<String>>, Error> {
+            Ok(aliases
+                .apply(df.clone().into(), point_in_time)?
+                .collect()?
+                .column("ticker")?
+                .utf8()?
+                .into_iter()
+                .map(|x| x.map(ToOwned::to_owned))
+                .collect())
+        };
+
+        assert_eq!(
+            ticker(false)?,
+            [Some("XYZ".to_owned()), Some("XYZ".to_owned())]
+        );
+        assert_eq!(
+            ticker(true)?,
+            [Some("SQ".to_owned()), Some("XYZ".to_owned())]
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::version(r#"{"version": 2, "aliases": []}"#)]
+    #[case::key(r#"{"version": 1, "aliases": [{"match": {"isin": "A"}, "ticker": "B"}]}"#)]
+    #[case::two_keys(
+        r#"{"version": 1, "aliases": [{"match": {"ticker": "A", "company": "A"}, "ticker": "B"}]}"#
+    )]
+    #[case::no_target(r#"{"version": 1, "aliases": [{"match": {"ticker": "A"}}]}"#)]
+    #[case::date(
+        r#"{"version": 1, "aliases": [{"match": {"ticker": "A"}, "ticker": "B", "from": "21/01/2025"}]}"#
+    )]
+    #[case::range(
+        r#"{"version": 1, "aliases": [{"match": {"ticker": "A"}, "ticker": "B", "from": "2025-01-21", "to": "2025-01-01"}]}"#
+    )]
+    fn invalid(#[case] json: &str) {
+        assert!(Aliases::parse(json).is_err());
+    }
+}
@@ -0,0 +1,530 @@
+use std::{collections::HashSet, env, fs, str::FromStr, sync::LazyLock};
+
+use anyhow::{Error, Result, anyhow};
+use polars::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::util::ticker::{DataSource, Ticker};
+
+const RULES_VERSION: u64 = 2;
+const DEFAULT_RULES: &str = include_str!("../../../rules/company.json");
+
+/// Company name rules, read once at startup from the file in
+/// `ARK_COMPANY_RULES` or the `rules/company.json` built into the binary
+pub static COMPANY_RULES: LazyLock<Rules> =
+    LazyLock::new(|| Rules::from_env().expect("Company name rules are invalid"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // plain substring
+    Literal,
+    Regex,
+    // only matches when surrounded by whitespace or the ends of the name
+    WholeToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    DataSource(DataSource),
+    Ticker(Ticker),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub order: i64,
+    pub kind: Kind,
+    pub scope: Scope,
+    pub find: String,
+    pub replace: String,
+    // only replace the first match
+    pub first_only: bool,
+    pub examples: Vec<Example>,
+}
+
+/// Legal suffixes and share classes, only removed as whole tokens at the end
+/// of a name. An entry starting with `-` only matches after a hyphen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Suffixes {
+    pub legal: Vec<String>,
+    pub share_class: Vec<String>,
+    pub examples: Vec<Example>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)]
+pub struct Rules {
+    pub version: u64,
+    pub suffixes: Suffixes,
+    // sorted by order
+    pub rules: Vec<Rule>,
+}
+
+impl Rules {
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var("ARK_COMPANY_RULES") {
+            Ok(path) => {
+                Self::parse(&fs::read_to_string(&path)?).map_err(|e| anyhow!("{path}: {e}"))
+            }
+            Err(_) => Self::parse(DEFAULT_RULES),
+        }
+    }
+
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        let json: Value = serde_json::from_str(json)?;
+
+        let version = json["version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Rules have no version"))?;
+        if version != RULES_VERSION {
+            return Err(anyhow!(
+                "Rules version {version} is not supported, expected {RULES_VERSION}"
+            ));
+        }
+
+        let mut rules = json["rules"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Rules have no rules array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Rule::parse(x).map_err(|e| anyhow!("Rule {i}: {e}")))
+            .collect::<Result<Vec<_>, Error>>()?;
+        rules.sort_by_key(|x| x.order);
+
+        let suffixes = &json["suffixes"];
+        let suffixes = Suffixes {
+            legal: strings(&suffixes["legal"]),
+            share_class: strings(&suffixes["share_class"]),
+            examples: examples(&suffixes["examples"]),
+        };
+
+        Ok(Self {
+            version,
+            suffixes,
+            rules,
+        })
+    }
+
+    /// Strips the suffixes, then every rule that applies to `ticker` in order,
+    /// then trailing whitespace is trimmed. Without a ticker only the rules
+    /// scoped to all run.
+    pub fn expr(&self, ticker: Option<Ticker>, column: Expr) -> Expr {
+        let suffixes = self.suffixes.clone();
+        let column = column.map(
+            move |s| {
+                let mut stripped: Utf8Chunked = s
+                    .utf8()?
+                    .into_iter()
+                    .map(|x| x.map(|x| suffixes.strip(x)))
+                    .collect();
+                stripped.rename(s.name());
+                Ok(Some(stripped.into_series()))
+            },
+            GetOutput::from_type(DataType::Utf8),
+        );
+
+        self.rules
+            .iter()
+            .filter(|x| x.applies_to(ticker))
+            .fold(column, |expr, rule| rule.expr(expr))
+            .str()
+            .rstrip(None)
+    }
+
+    /// Names from `names` that come out empty or with a word that was not in
+    /// the name and is not the replacement of a rule, e.g. "AFTKINGS" from
+    /// "DRAFTKINGS INC"
+    pub fn mangled(&self, ticker: Option<Ticker>, names: &[String]) -> Result<Vec<Example>, Error> {
+        let df = df!["company" => names]?
+            .lazy()
+            .select([self.expr(ticker, col("company"))])
+            .collect()?;
+
+        let renamed: HashSet<String> = self.rules.iter().flat_map(|x| words(&x.replace)).collect();
+
+        Ok(names
+            .iter()
+            .zip(df.column("company")?.utf8()?)
+            .filter_map(|(before, after)| {
+                let after = after.unwrap_or_default();
+                let known: HashSet<String> = words(before).collect();
+                let broken = after.trim().is_empty()
+                    || words(after).any(|x| !known.contains(&x) && !renamed.contains(&x));
+                broken.then(|| Example {
+                    before: before.clone(),
+                    after: after.to_owned(),
+                })
+            })
+            .collect())
+    }
+}
+
+// Words compared by Rules::mangled, without the punctuation the rules remove
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace()
+        .map(|x| x.replace(['-', ',', '.', '&'], ""))
+        .filter(|x| !x.is_empty())
+}
+
+fn strings(json: &Value) -> Vec<String> {
+    json.as_array()
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str().map(ToOwned::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn examples(json: &Value) -> Vec<Example> {
+    json.as_array()
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| {
+                    Some(Example {
+                        before: x["before"].as_str()?.to_owned(),
+                        after: x["after"].as_str()?.to_owned(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Suffixes {
+    // Compared without case, dots and trailing commas, "S.A." is "SA"
+    fn is_suffix(&self, token: &str) -> bool {
+        let token = token.replace('.', "").trim_end_matches(',').to_uppercase();
+        self.legal
+            .iter()
+            .chain(&self.share_class)
+            .any(|x| x.to_uppercase() == token)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.legal
+            .iter()
+            .chain(&self.share_class)
+            .map(|x| x.split_whitespace().count())
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Removes legal suffixes and share classes from the end of `name` until
+    /// none is left, "ALPHABET INC-CL A" is "ALPHABET". A suffix is never
+    /// removed from inside a word and the first word is always kept.
+    #[must_use]
+    pub fn strip(&self, name: &str) -> String {
+        let mut tokens: Vec<&str> = name.split_whitespace().collect();
+        while self.strip_last(&mut tokens) {}
+        tokens.join(" ")
+    }
+
+    fn strip_last(&self, tokens: &mut Vec<&str>) -> bool {
+        let len = tokens.len();
+        if len > 1 && tokens[len - 1] == "-" {
+            tokens.pop();
+            return true;
+        }
+        // the separator left behind by a share class, "INC- CLASS A"
+        if len > 1 && tokens[len - 1].len() > 1 && tokens[len - 1].ends_with('-') {
+            tokens[len - 1] = tokens[len - 1].trim_end_matches('-');
+            return true;
+        }
+
+        for n in (1..=self.max_tokens().min(len)).rev() {
+            let rest = tokens[len - n + 1..].join(" ");
+            let window = [tokens[len - n], &rest].join(" ");
+            if len > n && self.is_suffix(window.trim()) {
+                tokens.truncate(len - n);
+                return true;
+            }
+
+            // glued on with a hyphen, "INC-A" or "SEMICONDUCTOR-SP ADR"
+            let first = tokens[len - n];
+            let Some(i) = first.rfind('-') else {
+                continue;
+            };
+            let tail = match (&first[i..], rest.as_str()) {
+                (head, "") => head.to_owned(),
+                // a standalone hyphen, "INC - A"
+                ("-", rest) => format!("-{rest}"),
+                (head, rest) => format!("{head} {rest}"),
+            };
+            if !self.is_suffix(&tail) && !self.is_suffix(&tail[1..]) {
+                continue;
+            }
+            if i > 0 {
+                tokens[len - n] = &first[..i];
+                tokens.truncate(len - n + 1);
+                return true;
+            }
+            if len > n {
+                tokens.truncate(len - n);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Rule {
+    fn parse(json: &Value) -> Result<Self, Error> {
+        let text = |name: &str| {
+            json[name]
+                .as_str()
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| anyhow!("{name} is missing"))
+        };
+
+        let kind = match text("kind")?.as_str() {
+            "literal" => Kind::Literal,
+            "regex" => Kind::Regex,
+            "whole_token" => Kind::WholeToken,
+            x => return Err(anyhow!("{x} is not a rule kind")),
+        };
+
+        let scope = match &json["scope"] {
+            Value::Null => Scope::All,
+            Value::String(x) if x == "all" => Scope::All,
+            x if x["data_source"].is_string() => Scope::DataSource(
+                DataSource::from_str(x["data_source"].as_str().unwrap_or_default())
+                    .map_err(|_| anyhow!("{} is not a data source", x["data_source"]))?,
+            ),
+            x if x["ticker"].is_string() => Scope::Ticker(
+                Ticker::from_str(x["ticker"].as_str().unwrap_or_default())
+                    .map_err(|_| anyhow!("{} is not a ticker", x["ticker"]))?,
+            ),
+            x => return Err(anyhow!("{x} is not a scope")),
+        };
+
+        let rule = Self {
+            order: json["order"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("order is missing"))?,
+            kind,
+            scope,
+            find: text("find")?,
+            replace: text("replace")?,
+            first_only: json["first_only"].as_bool().unwrap_or(false),
+            examples: examples(&json["examples"]),
+        };
+
+        // a broken pattern should fail at startup, not in the middle of a run
+        if rule.kind != Kind::Literal {
+            Regex::new(&rule.pattern())?;
+        }
+        Ok(rule)
+    }
+
+    #[must_use]
+    pub fn applies_to(&self, ticker: Option<Ticker>) -> bool {
+        match self.scope {
+            Scope::All => true,
+            Scope::DataSource(data_source) => {
+                ticker.is_some_and(|x| x.data_source() == data_source)
+            }
+            Scope::Ticker(scope) => ticker == Some(scope),
+        }
+    }
+
+    fn pattern(&self) -> String {
+        match self.kind {
+            Kind::Literal | Kind::Regex => self.find.clone(),
+            Kind::WholeToken => format!(r"(^|\s){}(\s|$)", regex::escape(&self.find)),
+        }
+    }
+
+    fn replacement(&self) -> String {
+        match self.kind {
+            Kind::Literal | Kind::Regex => self.replace.clone(),
+            // the whitespace before the token is kept, the one after only if
+            // something replaces the token
+            Kind::WholeToken if self.replace.is_empty() => "${1}".to_owned(),
+            Kind::WholeToken => format!("${{1}}{}${{2}}", self.replace.replace('$', "$$")),
+        }
+    }
+
+    pub fn expr(&self, column: Expr) -> Expr {
+        let literal = self.kind == Kind::Literal;
+        let (pattern, replacement) = (lit(self.pattern()), lit(self.replacement()));
+        if self.first_only {
+            column.str().replace(pattern, replacement, literal)
+        } else {
+            column.str().replace_all(pattern, replacement, literal)
+        }
+    }
+
+    /// Runs only this rule on `text`, trimmed like [`Rules::expr`] does
+    pub fn apply(&self, text: &str) -> Result<String, Error> {
+        let df = df!["company" => [text]]?
+            .lazy()
+            .select([self.expr(col("company")).str().rstrip(None)])
+            .collect()?;
+        Ok(df
+            .column("company")?
+            .utf8()?
+            .get(0)
+            .unwrap_or_default()
+            .to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    // Shows the before and after of every example, run with --nocapture
+    #[test]
+    fn rule_examples() -> Result<(), Error> {
+        let rules = Rules::parse(DEFAULT_RULES)?;
+        for example in &rules.suffixes.examples {
+            let after = rules.suffixes.strip(&example.before);
+            println!("suffix: {:?} -> {after:?}", example.before);
+            assert_eq!(after, example.after, "suffixes on {:?}", example.before);
+        }
+        for rule in &rules.rules {
+            assert!(
+                !rule.examples.is_empty(),
+                "rule {} ({:?}) has no example",
+                rule.order,
+                rule.find
+            );
+            for example in &rule.examples {
+                let after = rule.apply(&example.before)?;
+                println!(
+                    "{:>5} {:?}: {:?} -> {after:?}",
+                    rule.order, rule.find, example.before
+                );
+                assert_eq!(
+                    after, example.after,
+                    "rule {} ({:?}) on {:?}",
+                    rule.order, rule.find, example.before
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::whole_token_end(Kind::WholeToken, "INC", "", "ROKU INC", "ROKU")]
+    #[case::whole_token_middle(Kind::WholeToken, "INC", "", "ROKU INC CL A", "ROKU CL A")]
+    #[case::whole_token_in_word(Kind::WholeToken, "INC", "", "INCYTE", "INCYTE")]
+    #[case::whole_token_replace(Kind::WholeToken, "&", "AND", "AT & T", "AT AND T")]
+    #[case::regex(Kind::Regex, r"\s+CL [A-C]$", "", "ALPHABET INC CL C", "ALPHABET INC")]
+    #[case::literal(Kind::Literal, ".", "", "AMAZON.COM", "AMAZONCOM")]
+    fn kinds(
+        #[case] kind: Kind,
+        #[case] find: &str,
+        #[case] replace: &str,
+        #[case] before: &str,
+        #[case] after: &str,
+    ) -> Result<(), Error> {
+        let rule = Rule {
+            order: 0,
+            kind,
+            scope: Scope::All,
+            find: find.to_owned(),
+            replace: replace.to_owned(),
+            first_only: false,
+            examples: vec![],
+        };
+        assert_eq!(rule.apply(before)?, after);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::draftkings("DRAFTKINGS INC", "DRAFTKINGS")]
+    #[case::tsm("TAIWAN SEMICONDUCTOR-SP ADR", "TAIWAN SEMICONDUCTOR")]
+    #[case::incyte("INCYTE CORP", "INCYTE")]
+    #[case::adr("SEA LTD-ADR", "SEA")]
+    #[case::hyphen_class("ROBINHOOD MARKETS INC - A", "ROBINHOOD MARKETS")]
+    #[case::dots("Spotify Technology S.A.", "Spotify Technology")]
+    #[case::hyphenated_name("COCA-COLA CO", "COCA-COLA")]
+    #[case::only_suffix("CORP", "CORP")]
+    fn normalize(#[case] before: &str, #[case] after: &str) -> Result<(), Error> {
+        let rules = Rules::parse(DEFAULT_RULES)?;
+        let df = df!["company" => [before]]?
+            .lazy()
+            .select([rules.expr(None, col("company"))])
+            .collect()?;
+        assert_eq!(df.column("company")?.utf8()?.get(0), Some(after));
+        Ok(())
+    }
+
+    #[test]
+    fn mangled() -> Result<(), Error> {
+        let rules = Rules::parse(
+            r#"{"version": 2, "rules": [
+                {"order": 1, "kind": "literal", "find": "DR", "replace": ""},
+                {"order": 2, "kind": "literal", "find": "Space Investment", "replace": "SpaceX"}
+            ]}"#,
+        )?;
+        let names = ["DRAFTKINGS", "Space Investment", "TESLA", "DR"].map(ToOwned::to_owned);
+
+        assert_eq!(
+            rules.mangled(None, &names)?,
+            vec![
+                Example {
+                    before: "DRAFTKINGS".to_owned(),
+                    after: "AFTKINGS".to_owned(),
+                },
+                Example {
+                    before: "DR".to_owned(),
+                    after: String::new(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scopes() -> Result<(), Error> {
+        let rules = Rules::parse(
+            r#"{"version": 2, "rules": [
+                {"order": 2, "kind": "literal", "find": "A", "replace": "", "scope": {"ticker": "ARKK"}},
+                {"order": 1, "kind": "literal", "find": "B", "replace": "", "scope": {"data_source": "ArkEurope"}},
+                {"order": 3, "kind": "literal", "find": "C", "replace": ""}
+            ]}"#,
+        )?;
+
+        assert_eq!(
+            rules.rules.iter().map(|x| x.order).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        let applies = |ticker| {
+            rules
+                .rules
+                .iter()
+                .map(|x| x.applies_to(ticker))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(applies(Some(Ticker::ARKK)), [false, true, true]);
+        assert_eq!(applies(Some(Ticker::EUROPE_ARKI)), [true, false, true]);
+        assert_eq!(applies(None), [false, false, true]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::version(r#"{"version": 1, "rules": []}"#)]
+    #[case::kind(
+        r#"{"version": 2, "rules": [{"order": 1, "kind": "glob", "find": "A", "replace": ""}]}"#
+    )]
+    #[case::regex(
+        r#"{"version": 2, "rules": [{"order": 1, "kind": "regex", "find": "(", "replace": ""}]}"#
+    )]
+    #[case::ticker(r#"{"version": 2, "rules": [{"order": 1, "kind": "literal", "find": "A", "replace": "", "scope": {"ticker": "NOPE"}}]}"#)]
+    fn invalid(#[case] json: &str) {
+        assert!(Rules::parse(json).is_err());
+    }
+}
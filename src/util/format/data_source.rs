@@ -1,20 +1,215 @@
-use anyhow::{Error, Result};
+use std::fmt;
+
+use anyhow::{Error, Result, anyhow};
+use chrono::NaiveDate;
 use polars::prelude::*;
 
-use crate::{ticker::DataSource, util::df::DF};
-
-pub fn data_source(data_source: DataSource, mut df: DF) -> Result<DF, Error> {
-    let df = match data_source {
-        DataSource::ArkVenture => df_format_arkvx(df)?,
-        DataSource::Ark => df,
-        DataSource::Shares21 => df_format_21shares(df)?,
-        DataSource::ArkEurope | DataSource::Rize => {
-            df = df_format_europe_csv(df)?;
-            df = df_format_europe_arkfundsio(df)?;
-            df_format_europe(df)?
+use crate::{
+    ticker::DataSource,
+    util::{data_reader::as_of_date, df::DF, format::Layout},
+};
+
+/// One column of the file a [`DataSource`] serves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawColumn {
+    pub name: &'static str,
+    // canonical column it is renamed to, None is dropped
+    pub canonical: Option<&'static str>,
+    pub required: bool,
+}
+
+const fn keep(name: &'static str, canonical: &'static str) -> RawColumn {
+    RawColumn {
+        name,
+        canonical: Some(canonical),
+        required: true,
+    }
+}
+
+const fn optional(name: &'static str, canonical: &'static str) -> RawColumn {
+    RawColumn {
+        name,
+        canonical: Some(canonical),
+        required: false,
+    }
+}
+
+const fn ignore(name: &'static str) -> RawColumn {
+    RawColumn {
+        name,
+        canonical: None,
+        required: false,
+    }
+}
+
+const ARK: &[RawColumn] = &[
+    keep("date", "date"),
+    ignore("fund"),
+    keep("company", "company"),
+    keep("ticker", "ticker"),
+    keep("cusip", "cusip"),
+    keep("shares", "shares"),
+    keep("market value ($)", "market_value"),
+    keep("weight (%)", "weight"),
+];
+
+const ARK_VENTURE: &[RawColumn] = &[
+    keep("date", "date"),
+    ignore("fund"),
+    keep("company", "company"),
+    keep("ticker", "ticker"),
+    keep("CUSIP", "cusip"),
+    optional("shares", "shares"),
+    optional("market value ($)", "market_value"),
+    keep("weight (%)", "weight"),
+];
+
+const SHARES21: &[RawColumn] = &[
+    ignore("Account"),
+    keep("Date", "date"),
+    keep("StockTicker", "ticker"),
+    keep("CUSIP", "cusip"),
+    keep("SecurityName", "company"),
+    keep("Shares", "shares"),
+    keep("Price", "share_price"),
+    keep("MarketValue", "market_value"),
+    keep("Weightings", "weight"),
+    ignore("NetAssets"),
+    ignore("SharesOutstanding"),
+    ignore("CreationUnits"),
+    // only used for the asset class
+    optional("MoneyMarketFlag", "money_market"),
+];
+
+const EUROPE: &[RawColumn] = &[
+    // as-of date from the title rows of the xlsx download
+    optional("date", "date"),
+    keep("name", "company"),
+    keep("ISIN", "cusip"),
+    ignore("Currency"),
+    keep("Weight", "weight"),
+];
+
+impl DataSource {
+    /// Input schema of the file served by [`crate::ticker::Ticker::get_url`],
+    /// and where every column ends up. Canonical columns not listed are
+    /// filled with nulls.
+    #[must_use]
+    pub const fn schema(self) -> &'static [RawColumn] {
+        match self {
+            Self::Ark => ARK,
+            Self::ArkVenture => ARK_VENTURE,
+            Self::Shares21 => SHARES21,
+            Self::ArkEurope | Self::Rize => EUROPE,
         }
-    };
-    Ok(df)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnMismatch {
+    Missing(&'static str),
+    Unexpected(String),
+}
+
+impl fmt::Display for ColumnMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(name) => write!(f, "missing column \"{name}\""),
+            Self::Unexpected(name) => write!(f, "unexpected column \"{name}\""),
+        }
+    }
+}
+
+/// Returned by [`data_source`] when a download does not match the schema of
+/// its [`DataSource`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaMismatch {
+    pub data_source: DataSource,
+    pub columns: Vec<ColumnMismatch>,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns: Vec<_> = self.columns.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{:?} schema mismatch: {}",
+            self.data_source,
+            columns.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+/// Formats a raw download of `data_source` strictly by its schema, anything
+/// else goes through [`Layout::detect`]
+pub fn data_source(data_source: DataSource, df: DF) -> Result<DF, Error> {
+    let df = df.collect()?;
+    // title rows leave no header to check, see df_format_europe_csv
+    if matches!(data_source, DataSource::ArkEurope | DataSource::Rize)
+        && matches!(Layout::detect(&df), Ok(Layout::EuropeCsv))
+    {
+        return df_format_europe_csv(df.into());
+    }
+
+    check_schema(data_source, &df)?;
+
+    let schema = data_source.schema();
+    let (old, new): (Vec<_>, Vec<_>) = schema
+        .iter()
+        .filter(|x| df.get_column_names().contains(&x.name))
+        .filter_map(|x| Some((x.name, x.canonical?)))
+        .unzip();
+    let mut df = df.select(&old)?.lazy().rename(&old, &new).collect()?;
+
+    // the Europe funds only have a date when the title rows had one
+    if !new.contains(&"date") {
+        return Err(anyhow!("{data_source:?} download has no as-of date"));
+    }
+
+    let nulls = [
+        ("ticker", Series::new("ticker", [None::<String>])),
+        ("market_value", Series::new("market_value", [None::<i64>])),
+        ("shares", Series::new("shares", [None::<i64>])),
+        ("share_price", Series::new("share_price", [None::<f64>])),
+    ];
+    for (name, series) in nulls {
+        // share_price is derived from market_value and shares in Ark::df_format
+        let derived = name == "share_price" && new.contains(&"market_value");
+        if !new.contains(&name) && !derived {
+            df = df.lazy().with_column(series.lit()).collect()?;
+        }
+    }
+
+    Ok(df.into())
+}
+
+/// Every column that is missing or not part of the schema, blank column names
+/// from trailing commas are ignored
+pub fn check_schema(data_source: DataSource, df: &DataFrame) -> Result<(), SchemaMismatch> {
+    let schema = data_source.schema();
+    let cols = df.get_column_names();
+
+    let mut columns: Vec<_> = schema
+        .iter()
+        .filter(|x| x.required && !cols.contains(&x.name))
+        .map(|x| ColumnMismatch::Missing(x.name))
+        .collect();
+    columns.extend(
+        cols.iter()
+            .filter(|&&x| !x.is_empty() && !schema.iter().any(|y| y.name == x))
+            .map(|&x| ColumnMismatch::Unexpected(x.to_owned())),
+    );
+
+    if columns.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaMismatch {
+            data_source,
+            columns,
+        })
+    }
 }
 
 pub fn df_format_21shares(df: DF) -> Result<DF, Error> {
@@ -51,7 +246,9 @@ pub fn df_format_21shares(df: DF) -> Result<DF, Error> {
         _ = df.drop_in_place("NetAssets");
         _ = df.drop_in_place("SharesOutstanding");
         _ = df.drop_in_place("CreationUnits");
-        _ = df.drop_in_place("MoneyMarketFlag");
+        if df.get_column_names().contains(&"MoneyMarketFlag") {
+            df.rename("MoneyMarketFlag", "money_market")?;
+        }
     }
 
     Ok(df.into())
@@ -67,19 +264,28 @@ pub fn df_format_arkvx(df: DF) -> Result<DF, Error> {
             .collect()?;
     }
 
+    Ok(with_market_columns(df)?.into())
+}
+
+pub fn df_format_nexveridian(df: DF) -> Result<DF, Error> {
+    Ok(with_market_columns(df.collect()?)?.into())
+}
+
+// ARKVX and the Europe funds don't report market value, shares or price
+fn with_market_columns(df: DataFrame) -> Result<DataFrame, Error> {
     let cols = df.get_column_names();
-    if !cols.contains(&"market value ($)") && !cols.contains(&"market_value") {
-        df = df
-            .lazy()
-            .with_columns([
-                Series::new("market_value", [None::<i64>]).lit(),
-                Series::new("shares", [None::<i64>]).lit(),
-                Series::new("share_price", [None::<f64>]).lit(),
-            ])
-            .collect()?;
+    if cols.contains(&"market value ($)") || cols.contains(&"market_value") {
+        return Ok(df);
     }
 
-    Ok(df.into())
+    Ok(df
+        .lazy()
+        .with_columns([
+            Series::new("market_value", [None::<i64>]).lit(),
+            Series::new("shares", [None::<i64>]).lit(),
+            Series::new("share_price", [None::<f64>]).lit(),
+        ])
+        .collect()?)
 }
 
 pub fn df_format_europe(df: DF) -> Result<DF, Error> {
@@ -88,6 +294,10 @@ pub fn df_format_europe(df: DF) -> Result<DF, Error> {
     if df.get_column_names().contains(&"Currency") {
         _ = df.drop_in_place("Currency");
 
+        if !df.get_column_names().contains(&"date") {
+            return Err(anyhow!("Europe holdings have no as-of date"));
+        }
+
         df = df
             .lazy()
             .rename(
@@ -95,7 +305,6 @@ pub fn df_format_europe(df: DF) -> Result<DF, Error> {
                 vec!["company", "cusip", "weight"],
             )
             .with_columns([
-                Series::new("date", [chrono::Local::now().date_naive()]).lit(),
                 Series::new("ticker", [None::<String>]).lit(),
                 Series::new("market_value", [None::<i64>]).lit(),
                 Series::new("shares", [None::<i64>]).lit(),
@@ -134,6 +343,7 @@ pub fn df_format_europe_csv(df: DF) -> Result<DF, Error> {
     let mut df = df.collect()?;
 
     if df.get_column_names().contains(&"_duplicated_0") {
+        let as_of = title_date(&df)?;
         df = df.slice(2, df.height());
 
         df = df
@@ -141,7 +351,7 @@ pub fn df_format_europe_csv(df: DF) -> Result<DF, Error> {
             .lazy()
             .rename(df.get_column_names(), ["company", "cusip", "weight"])
             .with_columns([
-                Series::new("date", [chrono::Local::now().date_naive()]).lit(),
+                Series::new("date", [as_of]).lit(),
                 Series::new("ticker", [None::<String>]).lit(),
                 Series::new("market_value", [None::<i64>]).lit(),
                 Series::new("shares", [None::<i64>]).lit(),
@@ -152,3 +362,166 @@ pub fn df_format_europe_csv(df: DF) -> Result<DF, Error> {
 
     Ok(df.into())
 }
+
+// The first title row of the Europe csv ends up as the column names, the
+// second one as the first row
+pub fn title_date(df: &DataFrame) -> Result<NaiveDate, Error> {
+    let first_row = df
+        .get_columns()
+        .iter()
+        .filter_map(|x| x.utf8().ok()?.get(0));
+
+    df.get_column_names()
+        .into_iter()
+        .chain(first_row)
+        .find_map(as_of_date)
+        .ok_or_else(|| anyhow!("Europe csv has no as-of date in its title rows"))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::util::data_reader::Reader;
+
+    fn shares21(names: [&str; 9]) -> Result<DataFrame, Error> {
+        let values = [
+            "ARKA",
+            "10/16/2026",
+            "BTC",
+            "-",
+            "Bitcoin",
+            "2",
+            "100000",
+            "200000",
+            "100",
+        ];
+        Ok(DataFrame::new(
+            names
+                .iter()
+                .zip(values)
+                .map(|(&name, value)| Series::new(name, [value]))
+                .collect(),
+        )?)
+    }
+
+    #[test]
+    fn shares21_schema() -> Result<(), Error> {
+        let df = shares21([
+            "Account",
+            "Date",
+            "StockTicker",
+            "CUSIP",
+            "SecurityName",
+            "Shares",
+            "Price",
+            "MarketValue",
+            "Weightings",
+        ])?;
+        let df = data_source(DataSource::Shares21, df.into())?.collect()?;
+
+        assert_eq!(
+            df.get_column_names(),
+            [
+                "date",
+                "ticker",
+                "cusip",
+                "company",
+                "shares",
+                "share_price",
+                "market_value",
+                "weight"
+            ]
+        );
+        assert_eq!(df.column("company")?.utf8()?.get(0), Some("Bitcoin"));
+        Ok(())
+    }
+
+    #[test]
+    fn shares21_mismatch() -> Result<(), Error> {
+        let df = shares21([
+            "Account",
+            "Date",
+            "Ticker",
+            "CUSIP",
+            "SecurityName",
+            "Shares",
+            "Price",
+            "MarketValue",
+            "Weight",
+        ])?;
+        let Err(err) = data_source(DataSource::Shares21, df.into()) else {
+            panic!("schema mismatch was not reported");
+        };
+
+        assert_eq!(
+            err.downcast_ref::<SchemaMismatch>(),
+            Some(&SchemaMismatch {
+                data_source: DataSource::Shares21,
+                columns: vec![
+                    ColumnMismatch::Missing("StockTicker"),
+                    ColumnMismatch::Missing("Weightings"),
+                    ColumnMismatch::Unexpected("Ticker".to_owned()),
+                    ColumnMismatch::Unexpected("Weight".to_owned()),
+                ],
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Shares21 schema mismatch: missing column \"StockTicker\", missing column \
+             \"Weightings\", unexpected column \"Ticker\", unexpected column \"Weight\""
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_is_checked() -> Result<(), Error> {
+        // a formatted frame is not an ARK download, it goes through Layout
+        let df = df![
+            "date" => ["2026-10-16"],
+            "ticker" => ["TSLA"],
+            "cusip" => ["88160R101"],
+            "company" => ["TESLA"],
+            "weight" => [10.5],
+        ]?;
+        let Err(err) = data_source(DataSource::Ark, df.into()) else {
+            panic!("schema mismatch was not reported");
+        };
+
+        assert!(err.is::<SchemaMismatch>());
+        Ok(())
+    }
+
+    #[test]
+    fn europe_csv_as_of_date() -> Result<(), Error> {
+        let csv = "ARK Artificial Intelligence & Robotics UCITS ETF,,,\n\
+                   Holdings as of 16/10/2026,,,\n\
+                   name,ISIN,Weight,\n\
+                   NVIDIA CORP,US67066G1040,5.12%,\n";
+        let df = Reader::Csv.parse(csv.as_bytes().to_vec())?;
+        let df = df_format_europe_csv(df.into())?.collect()?;
+
+        assert_eq!(
+            df.column("date")?
+                .date()?
+                .as_date_iter()
+                .collect::<Vec<_>>(),
+            [NaiveDate::from_ymd_opt(2026, 10, 16)]
+        );
+        assert_eq!(df.column("cusip")?.utf8()?.get(0), Some("US67066G1040"));
+        Ok(())
+    }
+
+    #[test]
+    fn europe_csv_without_date() -> Result<(), Error> {
+        let csv = "ARK Artificial Intelligence & Robotics UCITS ETF,,,\n\
+                   Holdings,,,\n\
+                   name,ISIN,Weight,\n\
+                   NVIDIA CORP,US67066G1040,5.12%,\n";
+        let df = Reader::Csv.parse(csv.as_bytes().to_vec())?;
+
+        assert!(df_format_europe_csv(df.into()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use anyhow::{Error, Result};
+use polars::prelude::*;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::util::{df::DF, format};
+
+/// Returned by [`Layout::detect`] when the columns match no known layout
+#[derive(Debug)]
+pub struct UnknownLayout(pub Vec<String>);
+
+impl fmt::Display for UnknownLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown layout with columns [{}]", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownLayout {}
+
+/// Every raw layout a download or an old file can come in. Variants are
+/// checked in order, so more specific signatures come first.
+#[derive(Debug, strum_macros::Display, EnumIter, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // Europe csv, title rows above the header give blank column names
+    EuropeCsv,
+    // Europe holdings with a named header row
+    EuropeExport,
+    Shares21,
+    ArkVxCsv,
+    // arkfunds.io json after the holdings are unnested
+    ArkFundsIoJson,
+    ArkCsv,
+    // api.NexVeridian.com json, also what is stored in the parquet files
+    NexVeridianJson,
+}
+
+impl Layout {
+    /// Columns that must all be present
+    #[must_use]
+    pub const fn signature(self) -> &'static [&'static str] {
+        match self {
+            Self::EuropeCsv => &["_duplicated_0"],
+            Self::EuropeExport => &["name", "ISIN", "Currency", "Weight"],
+            Self::Shares21 => &["StockTicker", "SecurityName", "Weightings"],
+            Self::ArkVxCsv => &["company", "CUSIP"],
+            Self::ArkFundsIoJson => &["fund", "cusip", "weight_rank"],
+            Self::ArkCsv => &["fund", "company", "ticker", "cusip"],
+            Self::NexVeridianJson => &["date", "ticker", "cusip", "company", "weight"],
+        }
+    }
+
+    pub fn detect(df: &DataFrame) -> Result<Self, Error> {
+        let cols = df.get_column_names();
+        Self::iter()
+            .find(|layout| layout.signature().iter().all(|x| cols.contains(x)))
+            .ok_or_else(|| UnknownLayout(cols.iter().map(ToString::to_string).collect()).into())
+    }
+
+    /// Runs the one formatter for this layout, the shared cleanup in
+    /// [`crate::util::Ark::df_format`] happens afterwards
+    pub fn format(self, df: DF) -> Result<DF, Error> {
+        match self {
+            Self::EuropeCsv => format::df_format_europe_csv(df),
+            Self::EuropeExport => format::df_format_europe(df),
+            Self::Shares21 => format::df_format_21shares(df),
+            Self::ArkVxCsv => format::df_format_arkvx(df),
+            Self::ArkFundsIoJson => format::df_format_europe_arkfundsio(df),
+            Self::ArkCsv => Ok(df),
+            Self::NexVeridianJson => format::df_format_nexveridian(df),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn columns(names: &[&str]) -> DataFrame {
+        DataFrame::new(
+            names
+                .iter()
+                .map(|&x| Series::new(x, [None::<String>]))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::ark_csv(
+        &["date", "fund", "company", "ticker", "cusip", "shares", "market value ($)", "weight (%)"],
+        Layout::ArkCsv
+    )]
+    #[case::ark_csv_old(
+        &["date", "fund", "company", "ticker", "cusip", "shares", "market_value_($)", "weight_(%)"],
+        Layout::ArkCsv
+    )]
+    #[case::arkvx_csv(&["date", "fund", "company", "ticker", "CUSIP", "weight (%)"], Layout::ArkVxCsv)]
+    #[case::shares21(
+        &["Account", "Date", "StockTicker", "CUSIP", "SecurityName", "Shares", "Price", "MarketValue", "Weightings"],
+        Layout::Shares21
+    )]
+    #[case::europe_csv(&["ARK Artificial Intelligence", "_duplicated_0", "_duplicated_1"], Layout::EuropeCsv)]
+    #[case::europe_export(&["name", "ISIN", "Currency", "Weight"], Layout::EuropeExport)]
+    #[case::arkfunds_io(
+        &["company", "cusip", "date", "fund", "market_value", "share_price", "shares", "ticker", "weight", "weight_rank"],
+        Layout::ArkFundsIoJson
+    )]
+    #[case::arkfunds_io_europe(
+        &["company", "cusip", "date", "fund", "weight", "weight_rank"],
+        Layout::ArkFundsIoJson
+    )]
+    #[case::nexveridian(
+        &["company", "cusip", "date", "market_value", "share_price", "shares", "ticker", "weight", "weight_rank"],
+        Layout::NexVeridianJson
+    )]
+    #[case::parquet(
+        &["date", "ticker", "cusip", "company", "market_value", "shares", "share_price", "weight"],
+        Layout::NexVeridianJson
+    )]
+    fn detect(#[case] names: &[&str], #[case] expected: Layout) -> Result<(), Error> {
+        assert_eq!(Layout::detect(&columns(names))?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_layout() {
+        let err = Layout::detect(&columns(&["symbol", "name"])).unwrap_err();
+        assert!(err.is::<UnknownLayout>());
+        assert_eq!(
+            err.to_string(),
+            "Unknown layout with columns [symbol, name]"
+        );
+    }
+}
@@ -1,4 +1,6 @@
+pub mod alias;
+pub mod company;
 pub mod data_source;
 pub use data_source::*;
-pub mod ticker;
-pub use ticker::*;
+pub mod layout;
+pub use layout::*;
@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+// Unscheduled NYSE closures
+const SPECIAL_CLOSURES: [(i32, u32, u32); 5] = [
+    (2007, 1, 2),
+    (2012, 10, 29),
+    (2012, 10, 30),
+    (2018, 12, 5),
+    (2025, 1, 9),
+];
+
+/// Weekdays the NYSE is open
+#[must_use]
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !is_holiday(date)
+}
+
+fn is_holiday(date: NaiveDate) -> bool {
+    let year = date.year();
+    if SPECIAL_CLOSURES
+        .iter()
+        .any(|&(y, m, d)| NaiveDate::from_ymd_opt(y, m, d) == Some(date))
+    {
+        return true;
+    }
+
+    let ymd = |m, d| NaiveDate::from_ymd_opt(year, m, d).unwrap();
+    let mut holidays = vec![
+        // a Saturday new year is not moved to the Friday before
+        ymd(1, 1),
+        nth_weekday(year, 1, Weekday::Mon, 3),
+        nth_weekday(year, 2, Weekday::Mon, 3),
+        easter(year) - Duration::days(2),
+        last_weekday(year, 5, Weekday::Mon),
+        observed(ymd(7, 4)),
+        nth_weekday(year, 9, Weekday::Mon, 1),
+        nth_weekday(year, 11, Weekday::Thu, 4),
+        observed(ymd(12, 25)),
+    ];
+    if ymd(1, 1).weekday() == Weekday::Sun {
+        holidays.push(ymd(1, 2));
+    }
+    if year >= 2022 {
+        holidays.push(observed(ymd(6, 19)));
+    }
+
+    holidays.contains(&date)
+}
+
+// Saturday holidays move to Friday, Sunday holidays to Monday
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Duration::days(1),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+const fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).unwrap()
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5)
+        .unwrap_or_else(|| nth_weekday(year, month, weekday, 4))
+}
+
+// Anonymous Gregorian algorithm
+#[allow(clippy::many_single_char_names)]
+const fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month.unsigned_abs(), day.unsigned_abs()).unwrap()
+}
+
+/// Trading days between the first and last date that have no rows
+#[must_use]
+pub fn missing_days(dates: &[NaiveDate]) -> Vec<NaiveDate> {
+    let dates: BTreeSet<_> = dates.iter().copied().collect();
+    let (Some(&first), Some(&last)) = (dates.first(), dates.last()) else {
+        return vec![];
+    };
+
+    first
+        .iter_days()
+        .take_while(|&x| x <= last)
+        .filter(|&x| is_trading_day(x) && !dates.contains(&x))
+        .collect()
+}
+
+/// Groups missing days into inclusive windows, days only separated by
+/// weekends or holidays end up in the same window
+#[must_use]
+pub fn windows(missing: &[NaiveDate]) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut windows: Vec<(NaiveDate, NaiveDate)> = vec![];
+    for &day in missing {
+        match windows.last_mut() {
+            Some((_, end))
+                if end
+                    .iter_days()
+                    .skip(1)
+                    .find(|&x| is_trading_day(x))
+                    .is_some_and(|next| next == day) =>
+            {
+                *end = day;
+            }
+            _ => windows.push((day, day)),
+        }
+    }
+    windows
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[rstest]
+    #[case::weekday(day(2026, 10, 16), true)]
+    #[case::saturday(day(2026, 10, 17), false)]
+    #[case::new_year_observed(day(2023, 1, 2), false)]
+    #[case::mlk(day(2026, 1, 19), false)]
+    #[case::good_friday(day(2026, 4, 3), false)]
+    #[case::good_friday_2024(day(2024, 3, 29), false)]
+    #[case::memorial(day(2026, 5, 25), false)]
+    #[case::juneteenth_observed(day(2026, 6, 19), false)]
+    #[case::juneteenth_before_2022(day(2020, 6, 19), true)]
+    #[case::independence_observed(day(2026, 7, 3), false)]
+    #[case::thanksgiving(day(2026, 11, 26), false)]
+    #[case::christmas(day(2026, 12, 25), false)]
+    #[case::carter(day(2025, 1, 9), false)]
+    fn trading_day(#[case] date: NaiveDate, #[case] expected: bool) {
+        assert_eq!(is_trading_day(date), expected);
+    }
+
+    #[test]
+    fn gap_windows() {
+        let dates = [
+            day(2026, 6, 29),
+            day(2026, 6, 30),
+            // 07-01, 07-02 missing, 07-03 is a holiday
+            day(2026, 7, 7),
+            // 07-08 missing
+            day(2026, 7, 9),
+            day(2026, 7, 9),
+        ];
+
+        let missing = missing_days(&dates);
+        assert_eq!(
+            missing,
+            vec![
+                day(2026, 7, 1),
+                day(2026, 7, 2),
+                day(2026, 7, 6),
+                day(2026, 7, 8)
+            ]
+        );
+        assert_eq!(
+            windows(&missing),
+            vec![
+                (day(2026, 7, 1), day(2026, 7, 6)),
+                (day(2026, 7, 8), day(2026, 7, 8))
+            ]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::BuildHasher,
+};
+
+use anyhow::{Error, Result};
+use polars::prelude::*;
+use strum_macros::Display;
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[strum(serialize_all = "lowercase")]
+pub enum IdType {
+    Cusip,
+    Isin,
+    Sedol,
+    Unknown,
+}
+
+impl IdType {
+    /// What `id` looks like from its length and characters, the check digit is
+    /// not verified
+    #[must_use]
+    pub fn shape(id: &str) -> Self {
+        if !id.is_ascii() {
+            return Self::Unknown;
+        }
+        let alnum = |x: &str| {
+            x.bytes()
+                .all(|x| x.is_ascii_digit() || x.is_ascii_uppercase())
+        };
+        match id.len() {
+            // US and Canadian issues, '*', '@' and '#' only show up in private
+            // placements
+            9 if id.bytes().all(|x| {
+                x.is_ascii_digit() || x.is_ascii_uppercase() || matches!(x, b'*' | b'@' | b'#')
+            }) =>
+            {
+                Self::Cusip
+            }
+            12 if id[..2].bytes().all(|x| x.is_ascii_uppercase())
+                && alnum(&id[2..11])
+                && id.as_bytes()[11].is_ascii_digit() =>
+            {
+                Self::Isin
+            }
+            7 if alnum(id) && !id.bytes().any(|x| b"AEIOU".contains(&x)) => Self::Sedol,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The type of `id` if its check digit is right, otherwise unknown
+    #[must_use]
+    pub fn of(id: &str) -> Self {
+        let shape = Self::shape(id);
+        let valid = match shape {
+            Self::Cusip => cusip_check_digit(&id[..8]) == Some(id.as_bytes()[8]),
+            Self::Isin => luhn(id),
+            Self::Sedol => sedol_check_digit(&id[..6]) == Some(id.as_bytes()[6]),
+            Self::Unknown => false,
+        };
+        if valid { shape } else { Self::Unknown }
+    }
+}
+
+// 0-9 are themselves, letters count on from 10
+fn char_value(x: u8) -> Option<u32> {
+    char::from(x).to_digit(36)
+}
+
+/// Check digit of the first 8 characters of a CUSIP, as an ascii digit
+#[must_use]
+pub fn cusip_check_digit(base: &str) -> Option<u8> {
+    let mut sum = 0;
+    for (i, x) in base.bytes().enumerate() {
+        let mut value = match x {
+            b'*' => 36,
+            b'@' => 37,
+            b'#' => 38,
+            x => char_value(x)?,
+        };
+        if i % 2 == 1 {
+            value *= 2;
+        }
+        sum += value / 10 + value % 10;
+    }
+    digit((10 - sum % 10) % 10)
+}
+
+fn sedol_check_digit(base: &str) -> Option<u8> {
+    const WEIGHTS: [u32; 6] = [1, 3, 1, 7, 3, 9];
+    let mut sum = 0;
+    for (x, weight) in base.bytes().zip(WEIGHTS) {
+        sum += char_value(x)? * weight;
+    }
+    digit((10 - sum % 10) % 10)
+}
+
+// Letters are expanded to two digits, then the whole number has to pass Luhn
+fn luhn(id: &str) -> bool {
+    let Some(digits) = id
+        .bytes()
+        .map(char_value)
+        .collect::<Option<Vec<_>>>()
+        .map(|x| x.iter().map(ToString::to_string).collect::<String>())
+    else {
+        return false;
+    };
+
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, x)| {
+            let x = u32::from(x - b'0');
+            if i % 2 == 1 {
+                let x = x * 2;
+                x / 10 + x % 10
+            } else {
+                x
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn digit(x: u32) -> Option<u8> {
+    char::from_digit(x, 10).and_then(|x| u8::try_from(x).ok())
+}
+
+/// ISIN of a CUSIP issued in `country`, the country code, the CUSIP and the
+/// Luhn check digit
+#[must_use]
+pub fn isin_of_cusip(cusip: &str, country: &str) -> Option<String> {
+    let base = format!("{country}{cusip}");
+    (b'0'..=b'9')
+        .map(|x| format!("{base}{}", char::from(x)))
+        .find(|x| IdType::of(x) == IdType::Isin)
+}
+
+/// ISIN of `id`, an ISIN is kept
+///
+/// A CUSIP takes the country of `known`, an ISIN already seen for it, ex from
+/// a Europe fund, or else US. US and Canadian issues both have numeric CUSIPs,
+/// so a Canadian CUSIP that was never seen as an ISIN comes out wrong, ex SHOP
+/// 82509L107 is CA82509L1076 and not US82509L1070. A CINS, a CUSIP starting
+/// with a letter, is issued outside North America and has no default.
+#[must_use]
+pub fn isin(id: &str, known: Option<&str>) -> Option<String> {
+    match IdType::of(id) {
+        IdType::Isin => Some(id.to_owned()),
+        IdType::Cusip => {
+            let country = known
+                .filter(|x| cusip(x).as_deref() == Some(id))
+                .map(|x| &x[..2]);
+            match country {
+                Some(country) => isin_of_cusip(id, country),
+                None if id.as_bytes()[0].is_ascii_digit() => isin_of_cusip(id, "US"),
+                None => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// CUSIP of `id`, a CUSIP is kept and a US or Canadian ISIN has its CUSIP as
+/// the 9 characters after the country
+#[must_use]
+pub fn cusip(id: &str) -> Option<String> {
+    match IdType::of(id) {
+        IdType::Cusip => Some(id.to_owned()),
+        IdType::Isin if matches!(&id[..2], "US" | "CA") => Some(id[2..11].to_owned()),
+        _ => None,
+    }
+}
+
+/// Polars expression with the [`isin`] of every value in `column`, `known` is
+/// keyed by CUSIP
+pub fn isin_expr<S>(column: Expr, known: HashMap<String, String, S>) -> Expr
+where
+    S: BuildHasher + Send + Sync + 'static,
+{
+    column.map(
+        move |s| {
+            let mut isins: Utf8Chunked = s
+                .utf8()?
+                .into_iter()
+                .map(|x| {
+                    let x = x?;
+                    isin(x, known.get(x).map(String::as_str))
+                })
+                .collect();
+            isins.rename("isin");
+            Ok(Some(isins.into_series()))
+        },
+        GetOutput::from_type(DataType::Utf8),
+    )
+}
+
+/// Polars expression with the [`IdType`] of every value in `column`
+pub fn id_type_expr(column: Expr) -> Expr {
+    column.map(
+        |s| {
+            let mut id_types: Utf8Chunked = s
+                .utf8()?
+                .into_iter()
+                .map(|x| Some(x.map_or(IdType::Unknown, IdType::of).to_string()))
+                .collect();
+            id_types.rename("id_type");
+            Ok(Some(id_types.into_series()))
+        },
+        GetOutput::from_type(DataType::Utf8),
+    )
+}
+
+/// How many identifiers of every type a file has, and the ones that look like
+/// a CUSIP, ISIN or SEDOL but fail the check digit
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub counts: BTreeMap<IdType, usize>,
+    // distinct, in order of appearance
+    pub bad_check_digit: Vec<String>,
+}
+
+impl Report {
+    pub fn new<'a>(ids: impl IntoIterator<Item = Option<&'a str>>) -> Self {
+        let mut report = Self::default();
+        for id in ids {
+            let id_type = id.map_or(IdType::Unknown, IdType::of);
+            *report.counts.entry(id_type).or_default() += 1;
+
+            if let Some(id) = id
+                && id_type == IdType::Unknown
+                && IdType::shape(id) != IdType::Unknown
+                && !report.bad_check_digit.iter().any(|x| x == id)
+            {
+                report.bad_check_digit.push(id.to_owned());
+            }
+        }
+        report
+    }
+
+    /// Report of the cusip column
+    pub fn from_df(df: &DataFrame) -> Result<Self, Error> {
+        Ok(Self::new(df.column("cusip")?.utf8()?))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(id_type, count)| format!("{count} {id_type}"))
+            .collect();
+        write!(f, "{}", counts.join(", "))?;
+        if !self.bad_check_digit.is_empty() {
+            write!(f, ", bad check digit: {}", self.bad_check_digit.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::cusip("88160R101", IdType::Cusip)]
+    #[case::cusip_letters("G7945M107", IdType::Cusip)]
+    #[case::cusip_bad_check("88160R102", IdType::Unknown)]
+    #[case::isin("US88160R1014", IdType::Isin)]
+    #[case::isin_europe("NL0010273215", IdType::Isin)]
+    #[case::isin_bad_check("US88160R1015", IdType::Unknown)]
+    #[case::sedol("B0YBKJ7", IdType::Sedol)]
+    #[case::sedol_bad_check("B0YBKJ8", IdType::Unknown)]
+    #[case::name("TESLA", IdType::Unknown)]
+    #[case::empty("", IdType::Unknown)]
+    fn id_type(#[case] id: &str, #[case] expected: IdType) {
+        assert_eq!(IdType::of(id), expected);
+    }
+
+    #[rstest]
+    #[case::cusip("88160R101", None, Some("US88160R1014"))]
+    #[case::cusip_canada("82509L107", Some("CA82509L1076"), Some("CA82509L1076"))]
+    // the country of another security is not used
+    #[case::cusip_other("82509L107", Some("CA88160R1010"), Some("US82509L1070"))]
+    #[case::cins("G7945M107", None, None)]
+    #[case::isin("NL0010273215", None, Some("NL0010273215"))]
+    #[case::isin_canada("CA82509L1076", None, Some("CA82509L1076"))]
+    #[case::bad_check("US88160R1015", None, None)]
+    #[case::name("TESLA", None, None)]
+    fn to_isin(#[case] id: &str, #[case] known: Option<&str>, #[case] expected: Option<&str>) {
+        assert_eq!(isin(id, known).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case::cusip("88160R101", Some("88160R101"))]
+    #[case::cins("G7945M107", Some("G7945M107"))]
+    #[case::isin_us("US88160R1014", Some("88160R101"))]
+    #[case::isin_canada("CA82509L1076", Some("82509L107"))]
+    #[case::isin_europe("NL0010273215", None)]
+    #[case::bad_check("88160R102", None)]
+    #[case::name("TESLA", None)]
+    fn to_cusip(#[case] id: &str, #[case] expected: Option<&str>) {
+        assert_eq!(cusip(id).as_deref(), expected);
+    }
+
+    #[test]
+    fn report() {
+        let report = Report::new([
+            Some("88160R101"),
+            Some("88160R101"),
+            Some("US88160R1015"),
+            Some("TESLA"),
+            None,
+        ]);
+
+        assert_eq!(
+            report.counts,
+            BTreeMap::from([(IdType::Cusip, 2), (IdType::Unknown, 3)])
+        );
+        assert_eq!(report.bad_check_digit, ["US88160R1015"]);
+        assert_eq!(
+            report.to_string(),
+            "2 cusip, 3 unknown, bad check digit: US88160R1015"
+        );
+    }
+}
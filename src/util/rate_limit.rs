@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Error, Result};
+use futures::future::BoxFuture;
+use reqwest::Url;
+use tokio::{
+    sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+use crate::util::transport::{Request, Response, Transport};
+
+/// One limiter for the whole process, every ticker shares the same budget per
+/// host
+pub static RATE_LIMIT: LazyLock<HostLimiter> = LazyLock::new(|| {
+    HostLimiter::new(RateLimit::from_env().expect("Env ARK_RATE_LIMIT_* is not a number"))
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    // 0 disables the spacing between requests
+    pub requests_per_minute: u32,
+    pub max_in_flight: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 20,
+            max_in_flight: 2,
+        }
+    }
+}
+
+impl RateLimit {
+    /// Reads `ARK_RATE_LIMIT_RPM` and `ARK_RATE_LIMIT_IN_FLIGHT`
+    pub fn from_env() -> Result<Self, Error> {
+        let mut limit = Self::default();
+        if let Ok(val) = env::var("ARK_RATE_LIMIT_RPM") {
+            limit.requests_per_minute = val.parse()?;
+        }
+        if let Ok(val) = env::var("ARK_RATE_LIMIT_IN_FLIGHT") {
+            limit.max_in_flight = val.parse::<usize>()?.max(1);
+        }
+        Ok(limit)
+    }
+
+    fn interval(self) -> Duration {
+        if self.requests_per_minute == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_mins(1) / self.requests_per_minute
+        }
+    }
+}
+
+struct Host {
+    in_flight: Arc<Semaphore>,
+    // Earliest time the next request may start
+    next: AsyncMutex<Instant>,
+}
+
+pub struct HostLimiter {
+    limit: RateLimit,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+impl HostLimiter {
+    #[must_use]
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until a request to the host of `url` is allowed, the request
+    /// counts as in flight until the permit is dropped
+    pub async fn acquire(&self, url: &str) -> Result<OwnedSemaphorePermit, Error> {
+        let name = Url::parse(url)?.host_str().unwrap_or_default().to_owned();
+        let host = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_insert_with(|| {
+                Arc::new(Host {
+                    in_flight: Arc::new(Semaphore::new(self.limit.max_in_flight)),
+                    next: AsyncMutex::new(Instant::now()),
+                })
+            })
+            .clone();
+
+        let permit = host.in_flight.clone().acquire_owned().await?;
+
+        let mut next = host.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.limit.interval();
+
+        Ok(permit)
+    }
+}
+
+/// Runs every request of the inner transport through a [`HostLimiter`]
+pub struct RateLimited<T> {
+    inner: T,
+    limiter: &'static HostLimiter,
+}
+
+impl<T: Transport> RateLimited<T> {
+    pub const fn new(inner: T, limiter: &'static HostLimiter) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl<T: Transport> Transport for RateLimited<T> {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        Box::pin(async move {
+            let _permit = self.limiter.acquire(&request.url).await?;
+            self.inner.get(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spaces_requests_per_host() -> Result<(), Error> {
+        let limiter = HostLimiter::new(RateLimit {
+            requests_per_minute: 1200,
+            max_in_flight: 1,
+        });
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            drop(
+                limiter
+                    .acquire("https://assets.ark-funds.com/a.csv")
+                    .await?,
+            );
+        }
+        let same_host = start.elapsed();
+
+        let start = Instant::now();
+        drop(
+            limiter
+                .acquire("https://api.nexveridian.com/ark_holdings")
+                .await?,
+        );
+        let other_host = start.elapsed();
+
+        // 1200 per minute is one every 50ms
+        assert!(same_host >= Duration::from_millis(100));
+        assert!(other_host < Duration::from_millis(50));
+        Ok(())
+    }
+}
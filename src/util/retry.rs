@@ -0,0 +1,236 @@
+use std::{env, time::Duration};
+
+use anyhow::{Error, Result, anyhow};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use rand::Rng;
+
+use crate::util::transport::{Request, Response, Transport};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // Total number of tries, including the first one
+    pub max_attempts: u32,
+    pub base: Duration,
+    pub cap: Duration,
+    pub retryable_status: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base: Duration::from_secs(2),
+            cap: Duration::from_mins(5),
+            retryable_status: vec![403, 408, 425, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads `ARK_RETRY_MAX_ATTEMPTS`, `ARK_RETRY_BASE_MS`, `ARK_RETRY_CAP_MS`
+    /// and `ARK_RETRY_STATUS` (comma separated), falling back to the defaults
+    pub fn from_env() -> Result<Self, Error> {
+        let mut policy = Self::default();
+
+        if let Ok(val) = env::var("ARK_RETRY_MAX_ATTEMPTS") {
+            policy.max_attempts = val.parse()?;
+        }
+        if let Ok(val) = env::var("ARK_RETRY_BASE_MS") {
+            policy.base = Duration::from_millis(val.parse()?);
+        }
+        if let Ok(val) = env::var("ARK_RETRY_CAP_MS") {
+            policy.cap = Duration::from_millis(val.parse()?);
+        }
+        if let Ok(val) = env::var("ARK_RETRY_STATUS") {
+            policy.retryable_status = val
+                .split(',')
+                .map(|x| x.trim().parse())
+                .collect::<Result<_, _>>()?;
+        }
+
+        if policy.max_attempts == 0 {
+            return Err(anyhow!("ARK_RETRY_MAX_ATTEMPTS must be at least 1"));
+        }
+        Ok(policy)
+    }
+
+    #[must_use]
+    pub fn is_retryable(&self, status: u16) -> bool {
+        self.retryable_status.contains(&status)
+    }
+
+    /// Full jitter, a random delay between zero and `base * 2^(attempt - 1)`,
+    /// never more than `cap`
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.cap);
+        let millis = u64::try_from(ceiling.as_millis()).unwrap_or(u64::MAX);
+        Duration::from_millis(rand::rng().random_range(0..=millis))
+    }
+
+    /// The `Retry-After` of the response, never more than `cap`, or the
+    /// backoff when the server did not send one
+    #[must_use]
+    pub fn delay(&self, response: &Response, attempt: u32) -> Duration {
+        retry_after(response).map_or_else(|| self.backoff(attempt), |x| x.min(self.cap))
+    }
+}
+
+/// Parses a `Retry-After` header, either delay-seconds or an HTTP date
+#[must_use]
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.header("retry-after")?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Retries the inner transport on errors and on retryable status codes
+pub struct Retry<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T: Transport> Retry<T> {
+    pub const fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<T: Transport> Transport for Retry<T> {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        Box::pin(async move {
+            let max = self.policy.max_attempts;
+            let mut attempt = 1;
+            loop {
+                let result = self.inner.get(request).await;
+                let delay = match &result {
+                    Ok(response) => {
+                        println!(
+                            "GET {} attempt {attempt}/{max}: {}",
+                            request.url, response.status
+                        );
+                        if response.is_success() || !self.policy.is_retryable(response.status) {
+                            return result;
+                        }
+                        self.policy.delay(response, attempt)
+                    }
+                    Err(e) => {
+                        eprintln!("GET {} attempt {attempt}/{max}: {e}", request.url);
+                        self.policy.backoff(attempt)
+                    }
+                };
+
+                if attempt >= max {
+                    return result;
+                }
+                println!("Retrying {} in {delay:?}", request.url);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+
+    struct Script(Mutex<Vec<u16>>);
+
+    impl Transport for Script {
+        fn get<'a>(&'a self, _: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+            let status = self.0.lock().unwrap().remove(0);
+            Box::pin(async move {
+                Ok(Response {
+                    status,
+                    ..Default::default()
+                })
+            })
+        }
+    }
+
+    fn instant() -> RetryPolicy {
+        RetryPolicy {
+            base: Duration::ZERO,
+            cap: Duration::ZERO,
+            max_attempts: 3,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::recovers(vec![503, 429, 200], 200, 0)]
+    #[case::gives_up(vec![503, 503, 503, 200], 503, 1)]
+    #[case::not_retryable(vec![404, 200], 404, 1)]
+    #[tokio::test]
+    async fn retries(
+        #[case] script: Vec<u16>,
+        #[case] status: u16,
+        #[case] left: usize,
+    ) -> Result<(), Error> {
+        let transport = Retry::new(Script(Mutex::new(script)), instant());
+        let response = transport.get(&Request::new("https://example.com")).await?;
+
+        assert_eq!(response.status, status);
+        assert_eq!(transport.inner.0.lock().unwrap().len(), left);
+        Ok(())
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy {
+            base: Duration::from_secs(1),
+            cap: Duration::from_secs(10),
+            ..Default::default()
+        };
+        for attempt in 1..=20 {
+            assert!(policy.backoff(attempt) <= Duration::from_secs(10));
+        }
+        assert!(policy.backoff(1) <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let response = |value: &str| Response {
+            status: 429,
+            headers: vec![("Retry-After".to_owned(), value.to_owned())],
+            body: vec![],
+        };
+
+        assert_eq!(retry_after(&response("90")), Some(Duration::from_secs(90)));
+        assert_eq!(
+            retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&response("soon")), None);
+    }
+
+    #[rstest]
+    #[case::short("90", Duration::from_secs(90))]
+    #[case::day("86400", Duration::from_mins(5))]
+    #[case::far_future("Fri, 01 Jan 2100 00:00:00 GMT", Duration::from_mins(5))]
+    fn retry_after_is_capped(#[case] value: &str, #[case] expected: Duration) {
+        let response = Response {
+            status: 503,
+            headers: vec![("Retry-After".to_owned(), value.to_owned())],
+            body: vec![],
+        };
+
+        assert_eq!(RetryPolicy::default().delay(&response, 1), expected);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{env, sync::LazyLock};
+
+/// Columns written by `Ark::df_format`, read once from the environment
+pub static SCHEMA: LazyLock<Schema> = LazyLock::new(Schema::from_env);
+
+// Columns every formatted file has, in order
+pub const COLUMNS: [&str; 8] = [
+    "date",
+    "ticker",
+    "cusip",
+    "company",
+    "market_value",
+    "shares",
+    "share_price",
+    "weight",
+];
+
+// Derived from the cusip column
+pub const ID_COLUMNS: [&str; 2] = ["id_type", "isin"];
+
+// Normalized column and the column that keeps the value from the source file
+pub const RAW_COLUMNS: [(&str, &str); 2] = [("company", "company_raw"), ("ticker", "ticker_raw")];
+
+// every flag is its own env variable
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Schema {
+    // company_raw and ticker_raw, off by default
+    pub raw_columns: bool,
+    // id_type and isin, off by default
+    pub id_columns: bool,
+    // asset_class, off by default
+    pub asset_class: bool,
+    // ticker aliases only rewrite rows inside their date range
+    pub point_in_time: bool,
+}
+
+impl Schema {
+    /// Reads `ARK_RAW_COLUMNS`, `ARK_ID_COLUMNS`, `ARK_ASSET_CLASS_COLUMN` and
+    /// `ARK_POINT_IN_TIME`
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            raw_columns: env::var("ARK_RAW_COLUMNS").is_ok_and(|v| v == "true"),
+            id_columns: env::var("ARK_ID_COLUMNS").is_ok_and(|v| v == "true"),
+            asset_class: env::var("ARK_ASSET_CLASS_COLUMN").is_ok_and(|v| v == "true"),
+            point_in_time: env::var("ARK_POINT_IN_TIME").is_ok_and(|v| v == "true"),
+        }
+    }
+
+    /// Every column in output order, without `market` the market value, shares
+    /// and price columns the oldest csv files don't have are left out
+    #[must_use]
+    pub fn columns(self, market: bool) -> Vec<&'static str> {
+        let mut columns: Vec<_> = COLUMNS
+            .into_iter()
+            .filter(|x| market || !matches!(*x, "market_value" | "shares" | "share_price"))
+            .collect();
+        if self.id_columns {
+            columns.extend(ID_COLUMNS);
+        }
+        if self.asset_class {
+            columns.push("asset_class");
+        }
+        if self.raw_columns {
+            columns.extend(RAW_COLUMNS.map(|(_, raw)| raw));
+        }
+        columns
+    }
+}
@@ -0,0 +1,562 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fs::{self, File},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Error, Result, anyhow};
+use chrono::{Duration, NaiveDate};
+use polars::prelude::*;
+use serde_json::Value;
+
+use crate::util::{identifier, ticker::Ticker};
+
+const OVERRIDES_VERSION: u64 = 1;
+
+// Every ticker writes to the same securities.parquet
+static SECURITIES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Tickers by identifier, from the US funds that publish both. The Europe and
+/// Rize funds only publish an ISIN, their tickers are looked up here.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SecurityMaster {
+    // keyed by CUSIP, or the identifier itself when it has none
+    tickers: HashMap<String, String>,
+    // US and Canadian ISINs published by a fund, keyed by CUSIP
+    isins: HashMap<String, String>,
+}
+
+// A US or Canadian ISIN and its CUSIP are the same security
+fn key(id: &str) -> String {
+    identifier::cusip(id).unwrap_or_else(|| id.to_owned())
+}
+
+impl SecurityMaster {
+    /// Adds every ticker and cusip pair of a formatted fund, the latest date
+    /// wins when a security changed its ticker
+    pub fn add(&mut self, df: &DataFrame) -> Result<(), Error> {
+        self.add_isins(df)?;
+        let df = df.sort(["date"], false, false)?;
+        let pairs = df
+            .column("ticker")?
+            .utf8()?
+            .into_iter()
+            .zip(df.column("cusip")?.utf8()?);
+        for (ticker, cusip) in pairs {
+            if let (Some(ticker), Some(cusip)) = (ticker, cusip)
+                && !ticker.is_empty()
+                && identifier::IdType::of(cusip) != identifier::IdType::Unknown
+            {
+                self.tickers.insert(key(cusip), ticker.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds every US and Canadian ISIN in the cusip column of a fund
+    pub fn add_isins(&mut self, df: &DataFrame) -> Result<(), Error> {
+        for id in df.column("cusip")?.utf8()?.into_iter().flatten() {
+            if identifier::IdType::of(id) == identifier::IdType::Isin
+                && let Some(cusip) = identifier::cusip(id)
+            {
+                self.isins.insert(cusip, id.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a local file of tickers that win over the ones from the funds,
+    /// `{"version": 1, "tickers": {"NL0010273215": "ASML"}}`
+    pub fn add_overrides(&mut self, path: &Path) -> Result<(), Error> {
+        let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let version = json["version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("{}: overrides have no version", path.display()))?;
+        if version != OVERRIDES_VERSION {
+            return Err(anyhow!(
+                "{}: overrides version {version} is not supported, expected {OVERRIDES_VERSION}",
+                path.display()
+            ));
+        }
+
+        let tickers = json["tickers"]
+            .as_object()
+            .ok_or_else(|| anyhow!("{}: overrides have no tickers object", path.display()))?;
+        for (id, ticker) in tickers {
+            let ticker = ticker
+                .as_str()
+                .ok_or_else(|| anyhow!("{}: {id} is not a string", path.display()))?;
+            self.tickers.insert(key(id), ticker.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Adds the canonical ticker and the ISIN of every security in the table
+    pub fn add_securities(&mut self, securities: &BTreeMap<String, Security>) {
+        for (id, security) in securities {
+            if let Some(ticker) = &security.ticker {
+                self.tickers.insert(id.clone(), ticker.clone());
+            }
+            if let Some(isin) = &security.isin {
+                self.isins.insert(id.clone(), isin.clone());
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn ticker(&self, id: &str) -> Option<&str> {
+        self.tickers.get(&key(id)).map(String::as_str)
+    }
+
+    /// Fills every null ticker by the identifier in the cusip column
+    pub fn fill(&self, df: DataFrame) -> Result<(DataFrame, Filled), Error> {
+        let mut filled = Filled::default();
+        let mut tickers: Utf8Chunked = df
+            .column("ticker")?
+            .cast(&DataType::Utf8)?
+            .utf8()?
+            .into_iter()
+            .zip(df.column("cusip")?.utf8()?)
+            .map(|(ticker, cusip)| {
+                if ticker.is_some() {
+                    return ticker.map(ToOwned::to_owned);
+                }
+                let found = cusip.and_then(|x| self.ticker(x));
+                if found.is_some() {
+                    filled.resolved += 1;
+                } else {
+                    filled.unresolved += 1;
+                }
+                found.map(ToOwned::to_owned)
+            })
+            .collect();
+        tickers.rename("ticker");
+
+        let mut df = df;
+        df.with_column(tickers.into_series())?;
+        Ok((df, filled))
+    }
+
+    /// Sets the isin column from the cusip column, a CUSIP takes the country
+    /// of the ISIN a fund published for it
+    pub fn fill_isins(&self, df: DataFrame) -> Result<DataFrame, Error> {
+        Ok(df
+            .lazy()
+            .with_column(identifier::isin_expr(col("cusip"), self.isins.clone()).alias("isin"))
+            .collect()?)
+    }
+}
+
+/// Rows without a ticker that [`SecurityMaster::fill`] found one for, and the
+/// ones it didn't
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Filled {
+    pub resolved: usize,
+    pub unresolved: usize,
+}
+
+impl fmt::Display for Filled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tickers resolved, {} unresolved",
+            self.resolved, self.unresolved
+        )
+    }
+}
+
+/// One row of securities.parquet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Security {
+    pub first_seen: NaiveDate,
+    pub last_seen: NaiveDate,
+    // ticker on the last day it was seen
+    pub ticker: Option<String>,
+    // every spelling observed
+    pub tickers: BTreeSet<String>,
+    pub companies: BTreeSet<String>,
+    pub funds: BTreeSet<String>,
+    // US or Canadian ISIN, when a fund published one
+    pub isin: Option<String>,
+}
+
+impl Security {
+    fn merge(&mut self, other: Self) {
+        self.isin = self.isin.take().or(other.isin);
+        self.first_seen = self.first_seen.min(other.first_seen);
+        if other.last_seen >= self.last_seen && other.ticker.is_some() {
+            self.ticker = other.ticker;
+        }
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.tickers.extend(other.tickers);
+        self.companies.extend(other.companies);
+        self.funds.extend(other.funds);
+    }
+}
+
+/// Every security a formatted fund held, keyed by CUSIP or the identifier
+/// itself when it has none
+///
+/// Holdings without a valid identifier, like "-" or a name in the cusip
+/// column, are keyed by fund and company.
+pub fn securities(fund: Ticker, df: &DataFrame) -> Result<BTreeMap<String, Security>, Error> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let df = df.sort(["date"], false, false)?;
+    let dates = df.column("date")?.date()?;
+    let tickers = df.column("ticker")?.cast(&DataType::Utf8)?;
+    let companies = df.column("company")?;
+
+    let mut securities: BTreeMap<String, Security> = BTreeMap::new();
+    let rows = dates
+        .into_iter()
+        .zip(tickers.utf8()?)
+        .zip(df.column("cusip")?.utf8()?)
+        .zip(companies.utf8()?);
+    for (((date, ticker), cusip), company) in rows {
+        let Some(date) = date else {
+            continue;
+        };
+        let id = match (cusip, company) {
+            (Some(cusip), _) if identifier::IdType::of(cusip) != identifier::IdType::Unknown => {
+                key(cusip)
+            }
+            (_, Some(company)) => format!("{fund}:{company}"),
+            (_, None) => continue,
+        };
+        let date = epoch + Duration::days(date.into());
+        let ticker = ticker.filter(|x| !x.is_empty()).map(ToOwned::to_owned);
+        let isin = cusip
+            .filter(|&x| identifier::IdType::of(x) == identifier::IdType::Isin && key(x) != x)
+            .map(ToOwned::to_owned);
+
+        securities
+            .entry(id)
+            .or_insert_with(|| Security {
+                first_seen: date,
+                last_seen: date,
+                ticker: None,
+                tickers: BTreeSet::new(),
+                companies: BTreeSet::new(),
+                funds: BTreeSet::from([fund.to_string()]),
+                isin: None,
+            })
+            .merge(Security {
+                first_seen: date,
+                last_seen: date,
+                ticker: ticker.clone(),
+                tickers: ticker.into_iter().collect(),
+                companies: company.map(ToOwned::to_owned).into_iter().collect(),
+                funds: BTreeSet::new(),
+                isin,
+            });
+    }
+    Ok(securities)
+}
+
+pub fn read_securities(path: &str) -> Result<BTreeMap<String, Security>, Error> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let df = ParquetReader::new(File::open(path)?).finish()?;
+    let set = |name: &str| -> Result<Vec<BTreeSet<String>>, Error> {
+        Ok(df
+            .column(name)?
+            .list()?
+            .into_iter()
+            .map(|x| {
+                x.and_then(|x| {
+                    x.utf8()
+                        .ok()
+                        .map(|x| x.into_iter().flatten().map(ToOwned::to_owned).collect())
+                })
+                .unwrap_or_default()
+            })
+            .collect())
+    };
+    let date = |name: &str| -> Result<Vec<NaiveDate>, Error> {
+        Ok(df
+            .column(name)?
+            .date()?
+            .into_iter()
+            .map(|x| epoch + Duration::days(x.unwrap_or_default().into()))
+            .collect())
+    };
+
+    // tables written before the isin column have none
+    let isins: Vec<Option<String>> = match df.column("isin") {
+        Ok(x) => x
+            .utf8()?
+            .into_iter()
+            .map(|x| x.map(ToOwned::to_owned))
+            .collect(),
+        Err(_) => vec![None; df.height()],
+    };
+
+    let rows = df
+        .column("id")?
+        .utf8()?
+        .into_iter()
+        .zip(df.column("ticker")?.utf8()?)
+        .zip(date("first_seen")?)
+        .zip(date("last_seen")?)
+        .zip(set("tickers")?)
+        .zip(set("companies")?)
+        .zip(set("funds")?)
+        .zip(isins);
+    Ok(rows
+        .filter_map(
+            |(((((((id, ticker), first_seen), last_seen), tickers), companies), funds), isin)| {
+                Some((
+                    id?.to_owned(),
+                    Security {
+                        first_seen,
+                        last_seen,
+                        ticker: ticker.map(ToOwned::to_owned),
+                        tickers,
+                        companies,
+                        funds,
+                        isin,
+                    },
+                ))
+            },
+        )
+        .collect())
+}
+
+fn write_securities(path: &str, securities: &BTreeMap<String, Security>) -> Result<(), Error> {
+    let list = |name: &str, f: fn(&Security) -> &BTreeSet<String>| {
+        Series::new(
+            name,
+            securities
+                .values()
+                .map(|x| Series::new("", f(x).iter().map(String::as_str).collect::<Vec<_>>()))
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let mut df = DataFrame::new(vec![
+        Series::new(
+            "id",
+            securities.keys().map(String::as_str).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "ticker",
+            securities
+                .values()
+                .map(|x| x.ticker.as_deref())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "first_seen",
+            securities
+                .values()
+                .map(|x| x.first_seen)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "last_seen",
+            securities.values().map(|x| x.last_seen).collect::<Vec<_>>(),
+        ),
+        list("tickers", |x| &x.tickers),
+        list("companies", |x| &x.companies),
+        list("funds", |x| &x.funds),
+        Series::new(
+            "isin",
+            securities
+                .values()
+                .map(|x| x.isin.as_deref())
+                .collect::<Vec<_>>(),
+        ),
+    ])?;
+    ParquetWriter::new(File::create(path)?).finish(&mut df)?;
+    Ok(())
+}
+
+/// Writes the table at `path` from every fund, if it doesn't exist yet. Right
+/// after a deploy it would otherwise only hold the first funds written.
+pub fn seed_securities(
+    path: &str,
+    funds: impl IntoIterator<Item = (Ticker, DataFrame)>,
+) -> Result<(), Error> {
+    let _lock = SECURITIES_LOCK
+        .lock()
+        .map_err(|_| anyhow!("securities lock is poisoned"))?;
+    if Path::new(path).exists() {
+        return Ok(());
+    }
+
+    let mut table: BTreeMap<String, Security> = BTreeMap::new();
+    for (fund, df) in funds {
+        for (id, security) in securities(fund, &df)? {
+            match table.get_mut(&id) {
+                Some(x) => x.merge(security),
+                None => {
+                    table.insert(id, security);
+                }
+            }
+        }
+    }
+    // no other fund yet, the table starts with the one being written
+    if table.is_empty() {
+        return Ok(());
+    }
+    write_securities(path, &table)
+}
+
+/// Merges every security of a formatted fund into the table at `path`, first
+/// and last seen are widened and every spelling is kept
+pub fn update_securities(path: &str, fund: Ticker, df: &DataFrame) -> Result<(), Error> {
+    let _lock = SECURITIES_LOCK
+        .lock()
+        .map_err(|_| anyhow!("securities lock is poisoned"))?;
+
+    let mut table = if Path::new(path).exists() {
+        read_securities(path)?
+    } else {
+        BTreeMap::new()
+    };
+    for (id, security) in securities(fund, df)? {
+        match table.get_mut(&id) {
+            Some(x) => x.merge(security),
+            None => {
+                table.insert(id, security);
+            }
+        }
+    }
+    write_securities(path, &table)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn securities_table() -> Result<(), Error> {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let dir = TestDir::new("securities")?;
+        let path = format!("{}/securities.parquet", dir.path());
+
+        update_securities(
+            &path,
+            Ticker::ARKK,
+            &df![
+                "date" => [day(2024, 1, 2), day(2025, 1, 21)],
+                "ticker" => ["SQ", "XYZ"],
+                "cusip" => ["852234103", "852234103"],
+                "company" => ["BLOCK INC", "BLOCK"],
+            ]?,
+        )?;
+        update_securities(
+            &path,
+            Ticker::EUROPE_ARKK,
+            &df![
+                "date" => [day(2023, 6, 1)],
+                "ticker" => [None::<&str>],
+                "cusip" => ["US8522341036"],
+                "company" => ["Block"],
+            ]?,
+        )?;
+        let table = read_securities(&path)?;
+
+        let set = |x: &[&str]| x.iter().map(|&x| x.to_owned()).collect::<BTreeSet<_>>();
+        assert_eq!(
+            table,
+            BTreeMap::from([(
+                "852234103".to_owned(),
+                Security {
+                    first_seen: day(2023, 6, 1),
+                    last_seen: day(2025, 1, 21),
+                    ticker: Some("XYZ".to_owned()),
+                    tickers: set(&["SQ", "XYZ"]),
+                    companies: set(&["BLOCK INC", "BLOCK", "Block"]),
+                    funds: set(&["ARKK", "EUROPE_ARKK"]),
+                    isin: Some("US8522341036".to_owned()),
+                }
+            )])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_ids() -> Result<(), Error> {
+        let day = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let table = securities(
+            Ticker::ARKVX,
+            &df![
+                "date" => [day; 4],
+                "ticker" => [None::<&str>, None, Some("TSLA"), None],
+                "cusip" => [Some("-"), Some(""), Some("TESLA"), None],
+                "company" => [Some("SpaceX"), Some("OpenAI"), Some("TESLA"), None],
+            ]?,
+        )?;
+
+        assert_eq!(
+            table.keys().collect::<Vec<_>>(),
+            ["ARKVX:OpenAI", "ARKVX:SpaceX", "ARKVX:TESLA"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fill() -> Result<(), Error> {
+        let mut master = SecurityMaster::default();
+        master.add(&df![
+            "date" => ["2024-01-01", "2025-01-21", "2024-01-01"],
+            "ticker" => [Some("SQ"), Some("XYZ"), None],
+            "cusip" => ["852234103", "852234103", "88160R101"],
+        ]?)?;
+
+        let dir = TestDir::new("security_master")?;
+        let overrides = format!("{}/tickers.json", dir.path());
+        fs::write(
+            &overrides,
+            r#"{"version": 1, "tickers": {"NL0010273215": "ASML"}}"#,
+        )?;
+        master.add_overrides(Path::new(&overrides))?;
+
+        let europe = df![
+            "ticker" => [None::<&str>, None, None, Some("TSLA")],
+            "cusip" => ["US8522341036", "NL0010273215", "DE0007164600", "US88160R1014"],
+        ]?;
+        let (df, filled) = master.fill(europe)?;
+
+        assert_eq!(
+            df.column("ticker")?.utf8()?.into_iter().collect::<Vec<_>>(),
+            [Some("XYZ"), Some("ASML"), None, Some("TSLA")]
+        );
+        assert_eq!(
+            filled,
+            Filled {
+                resolved: 2,
+                unresolved: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fill_isins() -> Result<(), Error> {
+        let mut master = SecurityMaster::default();
+        master.add_isins(&df![
+            "cusip" => ["CA82509L1076", "NL0010273215"],
+        ]?)?;
+
+        let df = master.fill_isins(df![
+            "cusip" => [Some("82509L107"), Some("88160R101"), Some("NL0010273215"), None],
+        ]?)?;
+
+        assert_eq!(
+            df.column("isin")?.utf8()?.into_iter().collect::<Vec<_>>(),
+            [
+                Some("CA82509L1076"),
+                Some("US88160R1014"),
+                Some("NL0010273215"),
+                None
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use strum_macros::EnumIter;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+use crate::util::upstream::{UPSTREAM, Upstream};
+
+#[derive(Debug, strum_macros::EnumString, Clone, Copy, PartialEq, Eq)]
 pub enum DataSource {
     ArkVenture,
     Ark,
@@ -9,8 +11,31 @@ pub enum DataSource {
     Rize,
 }
 
+impl DataSource {
+    /// Columns the header row of a download must contain, compared ignoring
+    /// case
+    #[must_use]
+    pub const fn header_columns(self) -> &'static [&'static str] {
+        match self {
+            Self::ArkVenture | Self::Ark => &["company", "cusip"],
+            Self::Shares21 => &["StockTicker", "SecurityName"],
+            Self::ArkEurope | Self::Rize => &["ISIN"],
+        }
+    }
+}
+
 #[allow(clippy::upper_case_acronyms, non_camel_case_types)]
-#[derive(Debug, Default, strum_macros::Display, EnumIter, Clone, Copy, PartialEq, Eq)]
+#[derive(
+    Debug,
+    Default,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    EnumIter,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
 pub enum Ticker {
     ARKVX,
 
@@ -111,22 +136,25 @@ impl Ticker {
 
     #[must_use]
     pub fn get_url(&self) -> String {
+        self.get_url_from(&UPSTREAM)
+    }
+
+    #[must_use]
+    pub fn get_url_from(&self, upstream: &Upstream) -> String {
         match self.data_source() {
-            DataSource::ArkVenture => format!(
-                "https://assets.ark-funds.com/fund-documents/funds-etf-csv/{}",
-                self.value()
-            ),
+            DataSource::ArkVenture => format!("{}/{}", upstream.ark_csv, self.value()),
             DataSource::Ark => format!(
-                "https://assets.ark-funds.com/fund-documents/funds-etf-csv/ARK_{}_ETF_{}_HOLDINGS.csv",
+                "{}/ARK_{}_ETF_{}_HOLDINGS.csv",
+                upstream.ark_csv,
                 self.value(),
                 self
             ),
-            DataSource::Shares21 => format!(
-                "https://cdn.21shares-funds.com/uploads/fund-documents/us-bank/holdings/product/current/{}-Export.csv",
-                self.value()
-            ),
+            DataSource::Shares21 => {
+                format!("{}/{}-Export.csv", upstream.shares21_csv, self.value())
+            }
             DataSource::ArkEurope | DataSource::Rize => format!(
-                "https://europe.ark-funds.com/funds/{}/full-fund-holdings-download/",
+                "{}/{}/full-fund-holdings-download/",
+                upstream.ark_europe,
                 self.value()
             ),
         }
@@ -162,6 +190,18 @@ mod tests {
         "https://europe.ark-funds.com/funds/cybersecurity-and-data-privacy/full-fund-holdings-download/"
     )]
     fn get_url(#[case] input: Ticker, #[case] expected: String) {
-        assert_eq!(input.get_url(), expected);
+        assert_eq!(input.get_url_from(&Upstream::default()), expected);
+    }
+
+    #[test]
+    fn get_url_from_mirror() {
+        let upstream = Upstream {
+            ark_csv: "http://localhost:8080/ark".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Ticker::ARKK.get_url_from(&upstream),
+            "http://localhost:8080/ark/ARK_INNOVATION_ETF_ARKK_HOLDINGS.csv"
+        );
     }
 }
@@ -0,0 +1,419 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Error, Result, anyhow};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: vec![],
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Anything that can answer a GET request. Every download made by [`crate::Ark`]
+/// goes through one of these, so the network can be swapped out for files on
+/// disk.
+pub trait Transport: Send + Sync {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        (**self).get(request)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    // Whole request, from connecting until the body is read
+    pub request: Duration,
+    pub connect: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            request: Duration::from_mins(1),
+            connect: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Reads `ARK_TIMEOUT_MS` and `ARK_CONNECT_TIMEOUT_MS`, falling back to the
+    /// defaults
+    pub fn from_env() -> Result<Self, Error> {
+        let mut timeouts = Self::default();
+
+        if let Ok(val) = env::var("ARK_TIMEOUT_MS") {
+            timeouts.request = Duration::from_millis(val.parse()?);
+        }
+        if let Ok(val) = env::var("ARK_CONNECT_TIMEOUT_MS") {
+            timeouts.connect = Duration::from_millis(val.parse()?);
+        }
+        Ok(timeouts)
+    }
+}
+
+/// Real network access through reqwest
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new() -> Result<Self, Error> {
+        Self::with_timeouts(Timeouts::from_env()?)
+    }
+
+    pub fn with_timeouts(timeouts: Timeouts) -> Result<Self, Error> {
+        let client = Client::builder()
+            .user_agent(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+                 Chrome/119.0.0.0 Safari/537.36",
+            )
+            .gzip(true)
+            .timeout(timeouts.request)
+            .connect_timeout(timeouts.connect)
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        Box::pin(async move {
+            let mut builder = self.client.get(&request.url);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            let response = builder.send().await?;
+
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
+                .iter()
+                .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_owned())))
+                .collect();
+            let body = response.bytes().await?.to_vec();
+
+            Ok(Response {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Serves files from a directory, named with [`fixture_name`]. A missing file
+/// is answered with a 404.
+pub struct FixtureTransport {
+    dir: PathBuf,
+}
+
+impl FixtureTransport {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        Box::pin(read_fixture(&self.dir, &request.url))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Forward to the inner transport and save every response
+    Record,
+    // Only answer from saved responses
+    Replay,
+}
+
+/// Records responses from an inner transport into a directory, or replays them
+/// later with the original status and headers.
+pub struct RecordReplayTransport {
+    dir: PathBuf,
+    mode: Mode,
+    inner: Arc<dyn Transport>,
+}
+
+impl RecordReplayTransport {
+    pub fn new(dir: impl Into<PathBuf>, mode: Mode, inner: Arc<dyn Transport>) -> Self {
+        Self {
+            dir: dir.into(),
+            mode,
+            inner,
+        }
+    }
+}
+
+impl Transport for RecordReplayTransport {
+    fn get<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+        Box::pin(async move {
+            match self.mode {
+                Mode::Replay => read_fixture(&self.dir, &request.url).await,
+                Mode::Record => {
+                    let response = self.inner.get(request).await?;
+                    write_fixture(&self.dir, &request.url, &response).await?;
+                    Ok(response)
+                }
+            }
+        })
+    }
+}
+
+/// Transport picked by `ARK_TRANSPORT`, the network when it is unset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Http,
+    // fixtures:<dir>, files named with fixture_name, no network
+    Fixtures(PathBuf),
+    // record:<dir>, the network, saving every response
+    Record(PathBuf),
+    // replay:<dir>, responses saved by record, no network
+    Replay(PathBuf),
+}
+
+impl Kind {
+    pub fn parse(val: &str) -> Result<Self, Error> {
+        let val = val.trim();
+        if val.is_empty() || val == "http" {
+            return Ok(Self::Http);
+        }
+
+        let (kind, dir) = val
+            .split_once(':')
+            .filter(|(_, dir)| !dir.is_empty())
+            .ok_or_else(|| {
+                anyhow!("ARK_TRANSPORT {val} has no directory, ex replay:data/fixtures")
+            })?;
+        match kind {
+            "fixtures" => Ok(Self::Fixtures(dir.into())),
+            "record" => Ok(Self::Record(dir.into())),
+            "replay" => Ok(Self::Replay(dir.into())),
+            _ => Err(anyhow!(
+                "ARK_TRANSPORT {kind} is not one of http, fixtures, record or replay"
+            )),
+        }
+    }
+
+    /// Reads `ARK_TRANSPORT`
+    pub fn from_env() -> Result<Self, Error> {
+        env::var("ARK_TRANSPORT").map_or(Ok(Self::Http), |val| Self::parse(&val))
+    }
+}
+
+/// File name a url is stored under by [`FixtureTransport`] and
+/// [`RecordReplayTransport`]
+///
+/// The readable part replaces every other character with `_`, so the first 12
+/// hex digits of the sha256 of the whole url are appended to keep urls like
+/// `?ticker=A_B` and `?ticker=A&B` apart.
+#[must_use]
+pub fn fixture_name(url: &str) -> String {
+    let flat: String = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(url));
+    format!("{flat}-{}", &hash[..12])
+}
+
+fn meta_path(body_path: &Path) -> PathBuf {
+    let mut name = body_path.as_os_str().to_owned();
+    name.push(".meta.json");
+    PathBuf::from(name)
+}
+
+async fn read_fixture(dir: &Path, url: &str) -> Result<Response, Error> {
+    let path = dir.join(fixture_name(url));
+    if !path.exists() {
+        return Ok(Response {
+            status: 404,
+            ..Default::default()
+        });
+    }
+
+    let mut response = Response {
+        status: 200,
+        headers: vec![],
+        body: fs::read(&path).await?,
+    };
+
+    let meta = meta_path(&path);
+    if meta.exists() {
+        let meta: Value = serde_json::from_slice(&fs::read(meta).await?)?;
+        response.status = meta["status"]
+            .as_u64()
+            .and_then(|x| u16::try_from(x).ok())
+            .ok_or_else(|| anyhow!("Fixture for {url} has no status"))?;
+        response.headers = meta["headers"]
+            .as_object()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_owned())))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    Ok(response)
+}
+
+async fn write_fixture(dir: &Path, url: &str, response: &Response) -> Result<(), Error> {
+    fs::create_dir_all(dir).await?;
+    let path = dir.join(fixture_name(url));
+    fs::write(&path, &response.body).await?;
+
+    let headers: serde_json::Map<String, Value> = response
+        .headers
+        .iter()
+        .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+        .collect();
+    let meta = json!({
+        "url": url,
+        "status": response.status,
+        "headers": headers,
+    });
+    fs::write(meta_path(&path), serde_json::to_vec_pretty(&meta)?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    struct Static(Response);
+
+    impl Transport for Static {
+        fn get<'a>(&'a self, _: &'a Request) -> BoxFuture<'a, Result<Response, Error>> {
+            Box::pin(async move { Ok(self.0.clone()) })
+        }
+    }
+
+    #[rstest]
+    #[case::unset("", Kind::Http)]
+    #[case::http("http", Kind::Http)]
+    #[case::fixtures("fixtures:data/fixtures", Kind::Fixtures("data/fixtures".into()))]
+    #[case::record("record:data/recorded", Kind::Record("data/recorded".into()))]
+    #[case::replay("replay:data/recorded", Kind::Replay("data/recorded".into()))]
+    fn kind(#[case] val: &str, #[case] expected: Kind) -> Result<(), Error> {
+        assert_eq!(Kind::parse(val)?, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::no_dir("replay")]
+    #[case::empty_dir("replay:")]
+    #[case::unknown("proxy:data")]
+    fn kind_invalid(#[case] val: &str) {
+        assert!(Kind::parse(val).is_err());
+    }
+
+    #[test]
+    fn fixture_name_is_flat() {
+        let name =
+            fixture_name("https://api.nexveridian.com/ark_holdings?ticker=ARKK&start=2000-01-01");
+        assert!(
+            name.starts_with("api.nexveridian.com_ark_holdings_ticker_ARKK_start_2000-01-01-"),
+            "{name}"
+        );
+        assert_eq!(
+            name,
+            fixture_name("https://api.nexveridian.com/ark_holdings?ticker=ARKK&start=2000-01-01")
+        );
+    }
+
+    #[rstest]
+    #[case::query("https://example.com/h?ticker=A_B", "https://example.com/h?ticker=A&B")]
+    #[case::path("https://example.com/a/b", "https://example.com/a_b")]
+    #[case::scheme("https://example.com/a", "http://example.com/a")]
+    fn fixture_name_no_collision(#[case] a: &str, #[case] b: &str) {
+        assert_ne!(fixture_name(a), fixture_name(b));
+    }
+
+    #[tokio::test]
+    async fn record_then_replay() -> Result<(), Error> {
+        let dir = TestDir::new("transport_record")?;
+        let url = "https://example.com/holdings.csv";
+        let recorded = Response {
+            status: 200,
+            headers: vec![("etag".to_owned(), "\"abc\"".to_owned())],
+            body: b"date,ticker\n".to_vec(),
+        };
+
+        let inner: Arc<dyn Transport> = Arc::new(Static(recorded.clone()));
+        RecordReplayTransport::new(dir.path(), Mode::Record, inner.clone())
+            .get(&Request::new(url))
+            .await?;
+        let replayed = RecordReplayTransport::new(dir.path(), Mode::Replay, inner)
+            .get(&Request::new(url))
+            .await?;
+        let missing = FixtureTransport::new(dir.path())
+            .get(&Request::new("https://example.com/x"))
+            .await?;
+
+        assert_eq!(replayed, recorded);
+        assert_eq!(missing.status, 404);
+        Ok(())
+    }
+}
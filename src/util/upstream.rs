@@ -0,0 +1,50 @@
+use std::{env, sync::LazyLock};
+
+/// Base urls of every source, read once from the environment
+pub static UPSTREAM: LazyLock<Upstream> = LazyLock::new(Upstream::from_env);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    // api.NexVeridian.com
+    pub api: String,
+    // arkfunds.io/api
+    pub arkfunds_io: String,
+    // ARK Invest csv downloads
+    pub ark_csv: String,
+    // 21Shares csv exports
+    pub shares21_csv: String,
+    // ARK Europe and Rize holdings downloads
+    pub ark_europe: String,
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Self {
+            api: "https://api.nexveridian.com".to_owned(),
+            arkfunds_io: "https://arkfunds.io/api/v2".to_owned(),
+            ark_csv: "https://assets.ark-funds.com/fund-documents/funds-etf-csv".to_owned(),
+            shares21_csv: "https://cdn.21shares-funds.com/uploads/fund-documents/us-bank/holdings/product/current".to_owned(),
+            ark_europe: "https://europe.ark-funds.com/funds".to_owned(),
+        }
+    }
+}
+
+impl Upstream {
+    /// Reads `ARK_API_URL`, `ARKFUNDSIO_API_URL`, `ARK_CSV_URL`,
+    /// `SHARES21_CSV_URL` and `ARK_EUROPE_URL`, unset ones keep the default
+    #[must_use]
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str, default: String| {
+            env::var(name).map_or(default, |val| val.trim_end_matches('/').to_owned())
+        };
+
+        Self {
+            api: var("ARK_API_URL", default.api),
+            arkfunds_io: var("ARKFUNDSIO_API_URL", default.arkfunds_io),
+            ark_csv: var("ARK_CSV_URL", default.ark_csv),
+            shares21_csv: var("SHARES21_CSV_URL", default.shares21_csv),
+            ark_europe: var("ARK_EUROPE_URL", default.ark_europe),
+        }
+    }
+}
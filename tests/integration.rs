@@ -4,14 +4,16 @@ use chrono::NaiveDate;
 use polars::datatypes::DataType;
 use pretty_assertions::assert_eq;
 
-#[test]
-fn get_api_arkk() -> Result<(), Error> {
+#[tokio::test]
+async fn get_api_arkk() -> Result<(), Error> {
     let df = Ark::new(
         Source::ApiIncremental,
         Ticker::ARKK,
         Some("data/test".to_owned()),
-    )?
-    .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None)?;
+    )
+    .await?
+    .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None, None)
+    .await?;
 
     let expected = [
         "company",
@@ -33,10 +35,12 @@ fn get_api_arkk() -> Result<(), Error> {
     Ok(())
 }
 
-#[test]
-fn get_api_arkf() -> Result<(), Error> {
-    let df = Ark::new(Source::Ark, Ticker::ARKF, Some("data/test".to_owned()))?
-        .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None)?;
+#[tokio::test]
+async fn get_api_arkf() -> Result<(), Error> {
+    let df = Ark::new(Source::Ark, Ticker::ARKF, Some("data/test".to_owned()))
+        .await?
+        .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None, None)
+        .await?;
 
     let expected = [
         "company",
@@ -58,15 +62,17 @@ fn get_api_arkf() -> Result<(), Error> {
     Ok(())
 }
 
-#[test]
-fn get_api_format_arkk() -> Result<(), Error> {
+#[tokio::test]
+async fn get_api_format_arkk() -> Result<(), Error> {
     let dfl = Ark::new(
         Source::ApiIncremental,
         Ticker::ARKK,
         Some("data/test".to_owned()),
-    )?
-    .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None)?;
-    let df = Ark::df_format(dfl.into(), None)?.collect()?;
+    )
+    .await?
+    .get_api(NaiveDate::from_ymd_opt(2023, 5, 18), None, None)
+    .await?;
+    let df = Ark::df_format(dfl.into(), None, None)?.collect()?;
 
     assert_eq!(
         (df.get_column_names(), df.dtypes(), df.shape().1 > 1),
@@ -97,15 +103,17 @@ fn get_api_format_arkk() -> Result<(), Error> {
     Ok(())
 }
 
-#[test]
-fn get_api_format_arkvx() -> Result<(), Error> {
+#[tokio::test]
+async fn get_api_format_arkvx() -> Result<(), Error> {
     let dfl = Ark::new(
         Source::ApiIncremental,
         Ticker::ARKVX,
         Some("data/test".to_owned()),
-    )?
-    .get_api(NaiveDate::from_ymd_opt(2023, 1, 1), None)?;
-    let df = Ark::df_format(dfl.into(), None)?.collect()?;
+    )
+    .await?
+    .get_api(NaiveDate::from_ymd_opt(2023, 1, 1), None, None)
+    .await?;
+    let df = Ark::df_format(dfl.into(), None, None)?.collect()?;
 
     assert_eq!(
         (df.get_column_names(), df.dtypes(), df.shape().1 > 1),